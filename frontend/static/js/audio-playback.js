@@ -59,6 +59,31 @@ function loadAudio(player_uuid, room_code, playFailCallback, fallback = true) {
 }
 
 
+// Swaps the playing song for a freshly downloaded one, e.g. a longer Heardle snippet, without
+// restarting the progress bar
+function reloadAudio(player_uuid, room_code) {
+    const volume = sound != null ? sound.volume() : (slider.value / 100);
+    const reloaded = new Howl({
+        src: ['/song/' + player_uuid + '/' + room_code + '/' + Math.floor(Math.random() * 10000)],
+        format: ['mp3'],
+        autoplay: false,
+        loop: true,
+        volume: volume,
+        preload: true,
+        onload: function () {
+            if (sound != null)
+                sound.stop();
+            sound = reloaded;
+            newSound = reloaded;
+            songId = sound.play();
+        },
+        onloaderror: function (id, err) {
+            console.log(err)
+        },
+    });
+}
+
+
 function playAudio(time = 0, roundTime) {
     if (sound !== null) {
         if (sound.playing(songId)) {
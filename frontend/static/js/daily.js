@@ -0,0 +1,91 @@
+let dailySession = null;
+let dailyPollInterval = null;
+
+function dailyRequest(method, url, data) {
+    const options = {method: method};
+    if (data) {
+        options.headers = {'Content-Type': 'application/x-www-form-urlencoded'};
+        options.body = new URLSearchParams(data);
+    }
+    return fetch(url, options).then(response => response.json().then(json => {
+        if (!response.ok) {
+            throw new Error(json['error'] || 'Something went wrong');
+        }
+        return json;
+    }));
+}
+
+function showDailyError(error) {
+    const box = document.getElementById('daily-error');
+    box.textContent = error.message;
+    box.style.display = 'block';
+}
+
+function showDailyState(data) {
+    if (data['finished']) {
+        clearInterval(dailyPollInterval);
+        document.getElementById('daily-audio').pause();
+        document.getElementById('daily-game').style.display = 'none';
+        document.getElementById('daily-final-points').textContent = data['points'];
+        document.getElementById('daily-finished').style.display = 'block';
+        return;
+    }
+
+    document.getElementById('daily-round').textContent = 'Song ' + data['round'] + ' of ' + data['num_rounds'];
+    document.getElementById('daily-seconds-left').textContent = data['seconds_left'];
+    document.getElementById('daily-points').textContent = data['points'];
+    document.getElementById('daily-hint').textContent = data['hint'];
+
+    const roundOver = data['song_was'] !== null;
+    document.getElementById('daily-guess').disabled = roundOver;
+    document.getElementById('daily-song-was').textContent = roundOver ? 'The song was: ' + data['song_was'] : '';
+    document.getElementById('daily-next').style.display = roundOver ? 'inline-block' : 'none';
+}
+
+function loadDailySong() {
+    const audio = document.getElementById('daily-audio');
+    audio.src = '/practice/' + dailySession + '/song?' + Math.floor(Math.random() * 10000);
+    audio.play().catch(() => {
+        // Autoplay blocked, the user can press play on the controls
+    });
+}
+
+document.addEventListener('DOMContentLoaded', () => {
+    document.getElementById('daily-form').addEventListener('submit', event => {
+        event.preventDefault();
+        const username = document.getElementById('daily-username').value;
+        dailyRequest('POST', '/daily', {username: username}).then(data => {
+            dailySession = data['session'];
+            document.getElementById('daily-error').style.display = 'none';
+            document.getElementById('daily-form').style.display = 'none';
+            document.getElementById('daily-game').style.display = 'block';
+            showDailyState(data);
+            loadDailySong();
+            dailyPollInterval = setInterval(() => {
+                dailyRequest('GET', '/practice/' + dailySession).then(showDailyState).catch(showDailyError);
+            }, 1000);
+        }).catch(showDailyError);
+    });
+
+    document.getElementById('daily-guess-form').addEventListener('submit', event => {
+        event.preventDefault();
+        const input = document.getElementById('daily-guess');
+        dailyRequest('POST', '/practice/' + dailySession + '/guess', {guess: input.value}).then(data => {
+            if (data['correct']) {
+                input.value = '';
+            } else {
+                input.select();
+            }
+            showDailyState(data);
+        }).catch(showDailyError);
+    });
+
+    document.getElementById('daily-next').addEventListener('click', () => {
+        dailyRequest('POST', '/practice/' + dailySession + '/next').then(data => {
+            showDailyState(data);
+            if (!data['finished']) {
+                loadDailySong();
+            }
+        }).catch(showDailyError);
+    });
+});
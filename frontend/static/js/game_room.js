@@ -19,6 +19,7 @@ document.addEventListener('DOMContentLoaded', () => {
     let notGuesserUuid = '';
     let playerSectionVisibleOffset = 0;
     let round = null;
+    let choiceMade = false; // Only in multiple-choice mode
 
     const roomOptionsButton = document.getElementById("roomOptionsSm");
     if (roomOptionsButton) {
@@ -90,6 +91,52 @@ document.addEventListener('DOMContentLoaded', () => {
         updatePlayerSection();
     }
 
+    const nextSnippetButton = document.getElementById('next-snippet-button');
+    if (nextSnippetButton) {
+        nextSnippetButton.addEventListener('click', function () {
+            socket.send(JSON.stringify({'type': 'next-snippet'}));
+        });
+    }
+
+    function setNextSnippetVisible(visible) {
+        document.getElementById('next-snippet-div').style.display = visible ? 'flex' : 'none';
+    }
+
+    function showChoiceOptions(options) {
+        const container = document.getElementById('choice-options');
+        container.innerHTML = '';
+        choiceMade = false;
+        options.forEach(function (option, index) {
+            const button = document.createElement('button');
+            button.type = 'button';
+            button.className = 'btn btn-outline-light btn-sm m-1';
+            button.textContent = option;
+            // Enabled once the round started, choices before that don't count
+            button.disabled = true;
+            button.addEventListener('click', function () {
+                socket.send(JSON.stringify({'type': 'choose-option', 'index': index}));
+                choiceMade = true;
+                button.className = 'btn btn-light btn-sm m-1';
+                setChoiceOptionsEnabled(false);
+            });
+            container.appendChild(button);
+        });
+        container.style.display = 'flex';
+    }
+
+    function setChoiceOptionsEnabled(enabled) {
+        const buttons = document.getElementById('choice-options').getElementsByTagName('button');
+        for (let i = 0; i < buttons.length; i++) {
+            buttons[i].disabled = !enabled;
+        }
+    }
+
+    function hideChoiceOptions() {
+        const container = document.getElementById('choice-options');
+        container.style.display = 'none';
+        container.innerHTML = '';
+    }
+
     function exitGame() {
         stopAudio();
         location.reload()
@@ -148,6 +195,8 @@ document.addEventListener('DOMContentLoaded', () => {
         if (data['state'] === 'game_ended') {
             stopAudio();
             createHintLine(" ");
+            hideChoiceOptions();
+            setNextSnippetVisible(false);
             postGameState();
 
             delayCallback(4000, function () {
@@ -167,6 +216,8 @@ document.addEventListener('DOMContentLoaded', () => {
         }
         if (data['state'] === "new_turn") {
             document.title = 'Game - New Round! - GuessTheSong.io';
+            hideChoiceOptions();
+            setNextSnippetVisible(false);
             if (data['not_guesser'] != null) {
                 notGuesserUuid = data['not_guesser'];
                 refreshState();
@@ -181,7 +232,12 @@ document.addEventListener('DOMContentLoaded', () => {
             inGameState();
         }
         if (data['state'] === 'scoreboard') {
-            if (data['round'] > data['max_rounds']) {
+            if (data['max_rounds'] === null) {
+                document.getElementById('roundInfo').innerText = 'Round ' + data['round'];
+                document.getElementById('roundInfoInner').innerText = 'Round ' + data['round'];
+
+                round = data['round'];
+            } else if (data['round'] > data['max_rounds']) {
                 document.getElementById('roundInfo').innerText = 'Game over!';
                 document.getElementById('roundInfoInner').innerText = 'Game over!';
 
@@ -193,6 +249,7 @@ document.addEventListener('DOMContentLoaded', () => {
                 round = data['round'] + ' / ' + data['max_rounds'];
             }
             parseScores(data['payload']);
+            parseTeamScores(data['teams']);
             $('#scoreboardModal').modal('show');
             animateScores();
         }
@@ -260,6 +317,12 @@ document.addEventListener('DOMContentLoaded', () => {
                 if (mainStarted) {
                     display.textContent = inGameTimer;
                 }
+                if (!choiceMade) {
+                    setChoiceOptionsEnabled(true);
+                }
+                if (gameMode === 'Heardle') {
+                    setNextSnippetVisible(true);
+                }
                 createHintLine(data['hint'], data['modified']);
 
                 if (round === null) {
@@ -271,6 +334,15 @@ document.addEventListener('DOMContentLoaded', () => {
             }
         }
 
+        if (data['state'] === 'snippet_unlocked') {
+            reloadAudio(uuid, room);
+            createToast('System says:', 'You can now hear ' + data['seconds'] + ' seconds of the song', 'info');
+        }
+
+        if (data['state'] === 'multiple_choice') {
+            showChoiceOptions(data['options']);
+        }
+
         if (data['state'] === 'emoteReaction') {
             const reaction = data['reaction'];
             const uuid = data['uuid'];
@@ -1029,7 +1101,7 @@ document.addEventListener('DOMContentLoaded', () => {
         }
     }
 
-    function setScoreboardItem(uuid, streak, playerName, score, position, maxPoints, pointIncrease) {
+    function setScoreboardItem(uuid, streak, playerName, score, position, maxPoints, pointIncrease, streakBonus, penalty) {
         function leaderAvatar() {
             const svgContainer = document.getElementById('leader-ava-svg');
             return svgContainer.cloneNode(true)
@@ -1070,6 +1142,18 @@ document.addEventListener('DOMContentLoaded', () => {
                 scoreP.setAttribute('data-content', '');
                 score.appendChild(scoreP);
             }
+            if (streakBonus > 0) {
+                const bonusP = document.createElement('pre');
+                bonusP.className = 'text-warning';
+                bonusP.innerText = '  +' + streakBonus + ' streak bonus  ';
+                score.appendChild(bonusP);
+            }
+            if (penalty > 0) {
+                const penaltyP = document.createElement('pre');
+                penaltyP.className = 'text-danger';
+                penaltyP.innerText = '  -' + penalty + ' wrong guesses  ';
+                score.appendChild(penaltyP);
+            }
 
             div.appendChild(name);
             div.appendChild(score);
@@ -1130,6 +1214,24 @@ document.addEventListener('DOMContentLoaded', () => {
         modalBody.appendChild(node);
     }
 
+    // Teams are sorted by points already
+    function parseTeamScores(teams) {
+        const list = document.getElementById('scoreboardTeams');
+        list.innerHTML = '';
+        list.style.display = teams.length === 0 ? 'none' : 'block';
+        for (const team of teams) {
+            const item = document.createElement('li');
+            item.className = 'list-group-item d-flex justify-content-between';
+            const name = document.createElement('span');
+            name.textContent = team['name'];
+            const points = document.createElement('span');
+            points.textContent = team['points'] + ' (+' + team['point_diff'] + ')';
+            item.appendChild(name);
+            item.appendChild(points);
+            list.appendChild(item);
+        }
+    }
+
     function parseScores(scores) {
         const modalBody = document.getElementById('scoreboardLeaders');
         while (modalBody.firstChild) {
@@ -1144,7 +1246,9 @@ document.addEventListener('DOMContentLoaded', () => {
                 data['points'],
                 i + 1,
                 scores[0]['points'],
-                data['point_diff']);
+                data['point_diff'],
+                data['streak_bonus'],
+                data['penalty']);
 
             const streak = parseInt(data['streak'], 10);
             const uuid = data['uuid'];
@@ -26,56 +26,28 @@ $('#table').DataTable({
         {
             data: 'emoji',
             render: function (data, type, row, meta) {
-                return '<p class="card-text" id="' + row['username'].toString() + '">😎</p>'
+                return '<p class="card-text">' + data + '</p>'
             },
             title: 'Emoji'
         },
         {
             data: 'username',
             render: function (data, type, row, meta) {
-                return data
+                return $('<div>').text(data).html()
             },
             title: 'Username'
         },
-        {
-            data: 'num_won',
-            render: function (data, type, row, meta) {
-                return data
-            },
-            title: 'Games Won'
-        },
-        {
-            data: 'num_played',
-            render: function (data, type, row, meta) {
-                return data
-            },
-            title: 'Games Played'
-        },
         {
             data: 'score',
             render: function (data, type, row, meta) {
                 return data
             },
-            title: 'Total Score <a href="#" data-toggle="tooltip" title="Score is calculated by taking into account the sum total of all games you played. Including how many players there were, the number of rounds, and the amount of time spent!">(?)</a>'
+            title: 'Score'
         },
     ],
-    order: [[5, 'desc']]
+    order: [[3, 'desc']]
 });
 
 $(document).ready(function () {
     $('[data-toggle="tooltip"]').tooltip();
 });
-
-document.addEventListener('DOMContentLoaded', () => {
-    for (let i = 0; i < dataFromServer.length; i++) {
-        updateEmojis(dataFromServer[i]["username"], dataFromServer[i]["emoji"]);
-    }
-
-    function updateEmojis(username, emoji) {
-        let preview = document.getElementById(username);
-        if (emoji >= 0 && emoji < emojiList.length) {
-            preview.innerText = emojiList[emoji];
-        }
-    }
-
-});
@@ -3,6 +3,13 @@ window.addEventListener("beforeunload", function () {
 });
 
 let lobbyOwner = null;
+let lobbyPlayerData = [];
+let lobbyTeams = [];
+
+function isHost() {
+    const me = lobbyPlayerData.find(p => p.uuid === uuid);
+    return uuid === lobbyOwner || (me !== undefined && me.co_host);
+}
 
 $(function () {
     $('[data-toggle="tooltip"]').tooltip()
@@ -10,13 +17,14 @@ $(function () {
 
 function playerJoinOrLeave(payload, owner) {
     lobbyOwner = owner;
+    lobbyPlayerData = payload || [];
     rebuildPlayerList(payload);
     buildPlayerList();
 
     const selector = $("#startGameButton");
     const button = document.getElementById("startGameButton");
     const button2 = document.getElementById("startGameSpinner");
-    if (uuid !== lobbyOwner) {
+    if (!isHost()) {
         selector.addClass("disabled");
         button.style.display = "none";
         button2.style.display = "block";
@@ -47,6 +55,13 @@ document.addEventListener('DOMContentLoaded', () => {
             playerJoinOrLeave(payload['payload'], payload['owner']);
         }
         if (data['state'] === 'player_data') {
+            playerJoinOrLeave(data['payload'], data['owner']);
+        }
+        if (data['state'] === 'team_data') {
+            // Only sent if the room has a team mode
+            lobbyTeams = data['payload'];
+            document.getElementById('team-controls').style.display = 'block';
+            buildTeamList();
         }
         if (data['state'] === 'joined') {
             const payload = data['payload'];
@@ -85,6 +100,29 @@ document.addEventListener('DOMContentLoaded', () => {
     });
 });
 
+function joinTeam(team) {
+    socket.send(JSON.stringify({"type": "join-team", "team": team}));
+}
+
+function buildTeamList() {
+    const list = document.getElementById('team-list');
+    list.innerHTML = '';
+    for (const team of lobbyTeams) {
+        const members = team.members
+            .map(member => lobbyPlayerData.find(p => p.uuid === member))
+            .filter(p => p !== undefined)
+            .map(p => p.emoji + ' ' + p.username);
+        const item = document.createElement('li');
+        const joinButton = document.createElement('button');
+        joinButton.className = 'btn btn-link btn-sm p-0 mr-2';
+        joinButton.textContent = team.name;
+        joinButton.onclick = () => joinTeam(team.name);
+        item.appendChild(joinButton);
+        item.appendChild(document.createTextNode(members.join(', ')));
+        list.appendChild(item);
+    }
+}
+
 function changeGameState() {
     socket.send('{"type": "start-game"}');
 }
@@ -99,8 +137,39 @@ function buildPlayerList() {
         const node = document.createElement("li");
         node.className = 'list-group-item  bg-light';
         node.id = player.uuid;
-        const textNode = document.createTextNode(player.emoji + " " + player.display_name);
+        const data = lobbyPlayerData.find(p => p.uuid === player.uuid);
+        let label = player.emoji + " " + player.display_name;
+        if (data !== undefined && data.team)
+            label += " [" + data.team + "]";
+        const textNode = document.createTextNode(label);
         node.appendChild(textNode);
+        if (uuid === lobbyOwner && player.uuid !== lobbyOwner && data !== undefined) {
+            const coHostButton = document.createElement("button");
+            coHostButton.className = 'btn btn-outline-secondary btn-sm float-right';
+            coHostButton.innerText = data.co_host ? 'Remove co-host' : 'Make co-host';
+            coHostButton.onclick = () => socket.send(JSON.stringify({
+                "type": data.co_host ? "revoke-co-host" : "grant-co-host",
+                "uuid": player.uuid,
+            }));
+            node.appendChild(coHostButton);
+            const transferButton = document.createElement("button");
+            transferButton.className = 'btn btn-outline-secondary btn-sm float-right';
+            transferButton.innerText = 'Make host';
+            transferButton.onclick = () => socket.send(JSON.stringify({
+                "type": "transfer-host",
+                "uuid": player.uuid,
+            }));
+            node.appendChild(transferButton);
+        }
+        if (isHost() && player.uuid !== lobbyOwner && player.uuid !== uuid) {
+            for (const [label, type] of [['Kick', 'kick-player'], ['Ban', 'ban-player']]) {
+                const removeButton = document.createElement("button");
+                removeButton.className = 'btn btn-outline-danger btn-sm float-right';
+                removeButton.innerText = label;
+                removeButton.onclick = () => socket.send(JSON.stringify({"type": type, "uuid": player.uuid}));
+                node.appendChild(removeButton);
+            }
+        }
         document.querySelector(".list-group").appendChild(node);
     }
 
@@ -132,6 +201,15 @@ function myFunction3() {
 }
 
 document.addEventListener('DOMContentLoaded', () => {
+    document.getElementById('join-team-button').onclick = () => {
+        const team = document.getElementById('team-name').value.trim();
+        if (team !== '')
+            joinTeam(team);
+    };
+    document.getElementById('leave-team-button').onclick = () => {
+        socket.send(JSON.stringify({"type": "leave-team"}));
+    };
+
     $(document).on("click", "#emoji-picker", function (e) {
         e.stopPropagation();
         $('.intercom-composer-emoji-popover').toggleClass("active");
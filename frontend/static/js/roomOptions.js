@@ -9,9 +9,8 @@ document.addEventListener('DOMContentLoaded', () => {
 function updateView() {
     const gameMode = document.getElementById('game_mode');
     const playlist = document.getElementById('playlist');
-    const explicit = document.getElementById('explicit');
 
-    if (gameMode === null || explicit === null || playlist === null) {
+    if (gameMode === null || playlist === null) {
         return;
     }
 
@@ -21,17 +20,7 @@ function updateView() {
             group.style.display = 'none';
     }
 
-    if (gameMode.value === 'Themes') {
-        document.getElementById('advancedSettingsButton').style.display = 'block';
-        document.getElementById('theme-group').style.display = 'block';
-    } else {
-        document.getElementById('advancedSettingsButton').style.display = 'none';
-        document.getElementById('theme-group').style.display = 'none';
-        document.getElementById('advancedSettings').className = 'collapse';
-    }
-
-    if (nonExplicitThemes.includes(playlist.value))
-        explicit.removeAttribute('disabled');
-    else
-        explicit.setAttribute('disabled', '');
+    // Every game mode needs songs and uses the round settings
+    document.getElementById('theme-group').style.display = 'block';
+    document.getElementById('advancedSettingsButton').style.display = 'block';
 }
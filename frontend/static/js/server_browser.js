@@ -45,6 +45,9 @@ $('#table').DataTable({
         {
             data: 'players',
             render: function (data, type, row, meta) {
+                if (type === "display" && row.max_players !== null) {
+                    return data + ' / ' + row.max_players;
+                }
                 return data
             },
             title: 'Players'
@@ -0,0 +1,67 @@
+use crate::song_provider::*;
+
+/// How many songs the daily challenge consists of
+pub const DAILY_NUM_SONGS: usize = 5;
+pub const DAILY_ROUND_TIME_SECS: u32 = 75;
+
+/// Days since the UNIX epoch. Used as the seed for the daily song selection
+pub fn today() -> u64 {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    since_epoch.as_secs() / (24 * 60 * 60)
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct DailyResult {
+    pub username: String,
+    pub emoji: String,
+    pub score: u32,
+}
+
+/// Identifies whose daily challenge attempt a practice session is
+pub struct DailyParticipant {
+    pub day: u64,
+    pub username: String,
+    pub emoji: String,
+}
+
+pub struct DailyChallenge {
+    pub day: u64,
+    pub songs: std::sync::Arc<Vec<Song>>,
+    /// Users who already started today's challenge, by user cookie
+    pub played: std::collections::HashSet<String>,
+    /// Same, by IP address, so that clearing cookies isn't enough to play again
+    pub played_ips: std::collections::HashSet<std::net::IpAddr>,
+    pub results: Vec<DailyResult>,
+}
+
+impl DailyChallenge {
+    pub async fn download(
+        spotify_client: std::sync::Arc<rspotify::ClientCredsSpotify>,
+        playlist_url: &str,
+        day: u64,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let songs = download_seeded(spotify_client, playlist_url, day, DAILY_NUM_SONGS).await?;
+        log::info!("Downloaded {} songs for daily challenge of day {}", songs.len(), day);
+
+        Ok(Self {
+            day,
+            songs: std::sync::Arc::new(songs),
+            played: std::collections::HashSet::new(),
+            played_ips: std::collections::HashSet::new(),
+            results: Vec::new(),
+        })
+    }
+
+    /// Records that the user started today's challenge. False if they (or someone else at their
+    /// IP address) already did
+    pub fn start(&mut self, user: &str, ip: std::net::IpAddr) -> bool {
+        if self.played.contains(user) || self.played_ips.contains(&ip) {
+            return false;
+        }
+        self.played.insert(user.to_owned());
+        self.played_ips.insert(ip);
+        true
+    }
+}
@@ -0,0 +1,96 @@
+use crate::utils::*;
+
+/// What route handlers return instead of panicking. Browsers get a page explaining what went
+/// wrong instead of a blank one, scripts get JSON
+#[derive(Debug)]
+pub enum Error {
+    /// Sends the user somewhere they can continue, e.g. to the join page if their cookie is stale
+    Redirect(String),
+    /// Error page for routes that browsers navigate to
+    Page { status: axum::http::StatusCode, message: std::borrow::Cow<'static, str> },
+    /// `{"error": message}` for routes that are fetched by scripts
+    Json { status: axum::http::StatusCode, message: std::borrow::Cow<'static, str> },
+    /// Page that shows the error itself, like the create room form with its error box filled in
+    Html { status: axum::http::StatusCode, html: String },
+}
+
+impl Error {
+    pub fn redirect(url: impl Into<String>) -> Self {
+        Self::Redirect(url.into())
+    }
+
+    pub fn page(
+        status: axum::http::StatusCode,
+        message: impl Into<std::borrow::Cow<'static, str>>,
+    ) -> Self {
+        Self::Page { status, message: message.into() }
+    }
+
+    pub fn json(
+        status: axum::http::StatusCode,
+        message: impl Into<std::borrow::Cow<'static, str>>,
+    ) -> Self {
+        Self::Json { status, message: message.into() }
+    }
+
+    /// Turns an error page into JSON, for routes that are fetched by scripts
+    pub fn into_json(self) -> Self {
+        match self {
+            Self::Page { status, message } => Self::Json { status, message },
+            other => other,
+        }
+    }
+}
+
+/// Missing or unreadable frontend files
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        log::error!("Couldn't read page: {}", e);
+        Self::page(
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong on our side",
+        )
+    }
+}
+
+impl From<axum::extract::rejection::FormRejection> for Error {
+    fn from(e: axum::extract::rejection::FormRejection) -> Self {
+        log::info!("Rejected form: {}", e);
+        Self::page(e.status(), "Some of the form fields are missing or invalid")
+    }
+}
+
+impl From<axum::extract::rejection::PathRejection> for Error {
+    fn from(e: axum::extract::rejection::PathRejection) -> Self {
+        log::info!("Rejected path: {}", e);
+        Self::page(axum::http::StatusCode::NOT_FOUND, "This page doesn't exist")
+    }
+}
+
+fn error_page(status: axum::http::StatusCode, message: &str) -> String {
+    let title = status.canonical_reason().unwrap_or("Error");
+    match std::fs::read_to_string("frontend/error.html") {
+        Ok(html) => {
+            html.replace("ERRORTITLE", title).replace("ERRORMESSAGE", &escape_html(message))
+        }
+        Err(e) => {
+            log::error!("Couldn't read error page: {}", e);
+            format!("<h1>{}</h1><p>{}</p>", title, escape_html(message))
+        }
+    }
+}
+
+impl axum::response::IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Error::Redirect(url) => axum::response::Redirect::to(&url).into_response(),
+            Error::Page { status, message } => {
+                (status, axum::response::Html(error_page(status, &message))).into_response()
+            }
+            Error::Json { status, message } => {
+                (status, axum::Json(serde_json::json!({ "error": message }))).into_response()
+            }
+            Error::Html { status, html } => (status, axum::response::Html(html)).into_response(),
+        }
+    }
+}
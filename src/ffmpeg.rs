@@ -0,0 +1,93 @@
+/// Pipes the audio through ffmpeg with the given output options and returns the resulting mp3.
+/// Fails if ffmpeg isn't installed or can't process the audio
+async fn run_ffmpeg(audio: &[u8], output_args: &[&str]) -> std::io::Result<Vec<u8>> {
+    use tokio::io::AsyncWriteExt as _;
+
+    let mut child = tokio::process::Command::new("ffmpeg")
+        .args(["-loglevel", "error"])
+        .args(["-i", "pipe:0"])
+        .args(output_args)
+        .args(["-f", "mp3", "pipe:1"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Must write concurrently with reading stdout, or both pipes may fill up and deadlock
+    let mut stdin = child.stdin.take().expect("we configured stdin to be piped");
+    let audio = audio.to_vec();
+    let writer = tokio::spawn(async move {
+        if let Err(e) = stdin.write_all(&audio).await {
+            log::warn!("failed to pipe audio into ffmpeg: {}", e);
+        }
+        // stdin is dropped here which signals EOF to ffmpeg
+    });
+
+    let output = child.wait_with_output().await?;
+    writer.await.expect("writer task panicked");
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("ffmpeg exited with {}", output.status)));
+    }
+    Ok(output.stdout)
+}
+
+/// Cuts the audio down to the first `seconds` seconds
+pub async fn clip(audio: &[u8], seconds: u32) -> std::io::Result<Vec<u8>> {
+    run_ffmpeg(audio, &["-t", &seconds.to_string()]).await
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioModifier {
+    Reversed,
+    SpedUp,
+    PitchShifted,
+    Muffled,
+}
+
+impl AudioModifier {
+    pub const ALL: &'static [Self] =
+        &[Self::Reversed, Self::SpedUp, Self::PitchShifted, Self::Muffled];
+
+    /// Decides the modifier for the next round, out of the ones the room plays with
+    pub fn pick(modifiers: &[Self]) -> Option<Self> {
+        (!modifiers.is_empty()).then(|| modifiers[fastrand::usize(..modifiers.len())])
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Reversed => "reversed",
+            Self::SpedUp => "sped up",
+            Self::PitchShifted => "pitch shifted",
+            Self::Muffled => "muffled",
+        }
+    }
+
+    fn ffmpeg_filter(self) -> &'static str {
+        match self {
+            Self::Reversed => "areverse",
+            Self::SpedUp => "atempo=1.5",
+            // Resampling at a higher rate raises pitch and tempo, so slow the tempo back down
+            Self::PitchShifted => "aresample=44100,asetrate=55125,aresample=44100,atempo=0.8",
+            Self::Muffled => "lowpass=f=500",
+        }
+    }
+}
+
+/// Reads the audio challenge setting of the create room form: "off", "random" (a different
+/// modifier every round) or a single modifier
+pub fn deserialize_audio_modifiers<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<AudioModifier>, D::Error> {
+    use serde::de::IntoDeserializer as _;
+    use serde::Deserialize as _;
+
+    match String::deserialize(deserializer)?.as_str() {
+        "off" => Ok(Vec::new()),
+        "random" => Ok(AudioModifier::ALL.to_vec()),
+        modifier => AudioModifier::deserialize(modifier.into_deserializer()).map(|m| vec![m]),
+    }
+}
+
+pub async fn apply_modifier(audio: &[u8], modifier: AudioModifier) -> std::io::Result<Vec<u8>> {
+    run_ffmpeg(audio, &["-af", modifier.ffmpeg_filter()]).await
+}
@@ -1,9 +1,15 @@
+mod daily;
+mod error;
+mod ffmpeg;
 mod hints;
+mod practice;
 mod room_flow;
 mod room_structs;
 mod routes;
+mod scoring;
 mod song_provider;
 mod spotify_playlist;
+mod themes;
 mod utils;
 mod ws_structs;
 mod ytdlp_download;
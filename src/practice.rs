@@ -0,0 +1,136 @@
+use crate::daily::*;
+use crate::hints::*;
+use crate::scoring::*;
+use crate::song_provider::*;
+
+#[derive(Clone)]
+pub enum PracticeSongs {
+    Playlist(std::sync::Arc<SongProvider>),
+    /// Predetermined songs, for the daily challenge
+    Fixed(std::sync::Arc<Vec<Song>>),
+}
+
+impl PracticeSongs {
+    /// None if the playlist has no playable song
+    pub async fn song_for_round(&self, round: u32) -> Option<Song> {
+        match self {
+            PracticeSongs::Playlist(song_provider) => song_provider.next().await,
+            PracticeSongs::Fixed(songs) => songs.get(round as usize).cloned(),
+        }
+    }
+}
+
+/// Single-player game without room, lobby or WebSocket. The client drives it via plain HTTP
+pub struct PracticeSession {
+    pub user: String,
+    pub playlist_url: String,
+    pub songs: PracticeSongs,
+    pub daily: Option<DailyParticipant>,
+    pub num_rounds: u32,
+    pub round_time_secs: u32,
+    pub last_activity: std::time::Instant,
+
+    pub current_round: u32, // zero-indexed
+    pub points: u32,
+    pub current_song: Song,
+    pub guessed: Option<u32>, // Points gained
+    round_start_time: std::time::Instant,
+    hints: Hints,
+    hint_timer: u32, // Hints::hint_at must be called sequentially, so we track where we are
+}
+
+#[derive(serde::Serialize)]
+pub struct PracticeState {
+    pub round: u32,
+    pub num_rounds: u32,
+    pub round_time: u32,
+    pub seconds_left: u32,
+    pub hint: String,
+    pub points: u32,
+    pub guessed: bool,
+    /// Only revealed once the round is over
+    pub song_was: Option<String>,
+}
+
+impl PracticeSession {
+    pub fn new(
+        user: String,
+        playlist_url: String,
+        songs: PracticeSongs,
+        daily: Option<DailyParticipant>,
+        first_song: Song,
+        num_rounds: u32,
+        round_time_secs: u32,
+    ) -> Self {
+        Self {
+            user,
+            playlist_url,
+            songs,
+            daily,
+            num_rounds,
+            round_time_secs,
+            last_activity: std::time::Instant::now(),
+            current_round: 0,
+            points: 0,
+            guessed: None,
+            round_start_time: std::time::Instant::now(),
+            hints: Hints::new(&first_song.title, round_time_secs),
+            hint_timer: round_time_secs,
+            current_song: first_song,
+        }
+    }
+
+    fn seconds_left(&self) -> u32 {
+        self.round_time_secs.saturating_sub(self.round_start_time.elapsed().as_secs() as u32)
+    }
+
+    pub fn round_over(&self) -> bool {
+        self.guessed.is_some() || self.seconds_left() == 0
+    }
+
+    pub fn is_last_round(&self) -> bool {
+        self.current_round + 1 >= self.num_rounds
+    }
+
+    pub fn state(&mut self) -> PracticeState {
+        let seconds_left = self.seconds_left();
+        let mut hint = self.hints.hint_at(self.hint_timer);
+        while self.hint_timer > seconds_left {
+            self.hint_timer -= 1;
+            hint = self.hints.hint_at(self.hint_timer);
+        }
+
+        PracticeState {
+            round: self.current_round + 1,
+            num_rounds: self.num_rounds,
+            round_time: self.round_time_secs,
+            seconds_left,
+            hint,
+            points: self.points,
+            guessed: self.guessed.is_some(),
+            song_was: self.round_over().then(|| self.current_song.title.clone()),
+        }
+    }
+
+    /// Returns the points gained if the guess was correct
+    pub fn guess(&mut self, input: &str) -> Option<u32> {
+        if self.round_over() || !title_matches(&self.current_song.title, input) {
+            return None;
+        }
+
+        let guess_time = self.round_start_time.elapsed().as_secs_f32();
+        let points = points_for_guess(guess_time, self.round_time_secs, 0);
+        self.guessed = Some(points);
+        self.points += points;
+        Some(points)
+    }
+
+    pub fn start_next_round(&mut self, song: Song) {
+        self.current_round += 1;
+        self.guessed = None;
+        self.round_start_time = std::time::Instant::now();
+        self.hints = Hints::new(&song.title, self.round_time_secs);
+        self.hint_timer = self.round_time_secs;
+        self.current_song = song;
+    }
+}
@@ -1,16 +1,87 @@
+use crate::ffmpeg::*;
 use crate::hints::*;
 use crate::room_structs::*;
+use crate::scoring::*;
+use crate::song_provider::*;
 use crate::utils::*;
 use crate::ws_structs::*;
 
+/// Downloads the next song and applies the audio modifier, if any. Returns the modifier that was
+/// actually applied, which is None if ffmpeg failed. None if the playlist has no playable song
+async fn next_song(
+    song_provider: &SongProvider,
+    modifier: Option<AudioModifier>,
+) -> Option<(Song, Option<AudioModifier>)> {
+    let mut song = song_provider.next().await?;
+    let Some(modifier) = modifier else {
+        return Some((song, None));
+    };
+    match apply_modifier(&song.audio, modifier).await {
+        Ok(audio) => {
+            song.audio = audio;
+            Some((song, Some(modifier)))
+        }
+        Err(e) => {
+            log::error!("Couldn't apply audio modifier, playing the song as is: {}", e);
+            Some((song, None))
+        }
+    }
+}
+
+/// Sends everyone back to the lobby and resets the game
+fn end_game(room: &mut Room) {
+    room.send_all(&SendEvent::GameEnded);
+    for player in &mut room.players {
+        player.reset_round();
+        player.eliminated = false;
+        player.streak = 0;
+        player.points = 0;
+    }
+    for team in &mut room.teams {
+        team.points = 0;
+        team.point_diff = 0;
+    }
+    room.current_round = 0;
+    room.rounds_without_elimination = 0;
+    room.current_song = None;
+    room.current_modifier = None;
+    room.choices.clear();
+    room.snippets.clear();
+    room.round_start_time = None;
+    room.state = RoomState::Lobby;
+}
+
+fn end_game_without_songs(room: &mut Room) {
+    room.send_all(&SendEvent::Notify {
+        message: "The playlist has no songs without explicit content".into(),
+    });
+    end_game(room);
+}
+
 async fn finalize_round_and_kick_off_next_maybe(room: &parking_lot::Mutex<Room>) {
-    let song_provider = {
+    let (song_provider, modifier) = {
         let mut room = room.lock();
+        let room = &mut *room; // Allow borrowing different fields mutably at the same time
 
-        // Add up points and streak
+        // Skip votes and the round timer may both get here, only the first one ends the round
+        if room.state != RoomState::RoundStarted {
+            return;
+        }
+        room.state = RoomState::RoundEnded;
+
+        // Add up points and streak, for players and teams
+        for team in &mut room.teams {
+            team.point_diff = 0;
+        }
         for p in &mut room.players {
-            if let Some(new_points) = p.guessed {
-                p.points += new_points;
+            let new_points = p.points_after_round();
+            if let Some(team) = room.teams.iter_mut().find(|t| p.team.as_ref() == Some(&t.name)) {
+                team.points = (team.points + new_points).saturating_sub(p.points);
+                team.point_diff += new_points.saturating_sub(p.points);
+            }
+            p.points = new_points;
+
+            if p.guessed.is_some() && !p.guessed_by_teammate {
                 p.streak += 1;
             } else {
                 p.streak = 0;
@@ -18,48 +89,109 @@ async fn finalize_round_and_kick_off_next_maybe(room: &parking_lot::Mutex<Room>)
         }
         // Purge disconnected players
         room.players.retain(|p| p.ws.lock().is_some());
+        room.hand_over_ownership_if_needed();
+
+        if room.game_mode == GameMode::Elimination {
+            if eliminate_players(room) {
+                room.rounds_without_elimination = 0;
+            } else {
+                room.rounds_without_elimination += 1;
+            }
+        }
+        // After elimination, players from the waiting list haven't had a chance to guess yet
+        room.admit_queued_players();
 
         // Show scoreboard
-        let song_title = &room.current_song.as_ref().unwrap().title;
-        room.send_all(&SendEvent::Notify { message: format!("The song was: {}", song_title) });
-        let mut scoreboard_entries =
-            room.players.iter().map(|p| p.to_scoreboard_player()).collect::<Vec<_>>();
+        let song = room.current_song.as_ref().unwrap();
+        let mut message = match (room.game_mode, song.release_year) {
+            (GameMode::ReleaseYear, Some(year)) => {
+                format!("The song was: {} (released {})", song.title, year)
+            }
+            _ => format!("The song was: {}", song.title),
+        };
+        if let Some(modifier) = room.current_modifier {
+            message += &format!(", played {}", modifier.name());
+        }
+        room.send_all(&SendEvent::Notify { message });
+        let mut scoreboard_entries = room
+            .players
+            .iter()
+            .filter(|p| !p.spectator)
+            .map(|p| p.to_scoreboard_player())
+            .collect::<Vec<_>>();
         scoreboard_entries.sort_by_key(|player| std::cmp::Reverse(player.points));
+        let mut scoreboard_teams =
+            room.teams.iter().map(|t| t.to_scoreboard_team()).collect::<Vec<_>>();
+        scoreboard_teams.sort_by_key(|team| std::cmp::Reverse(team.points));
         room.send_all(&SendEvent::Scoreboard {
             round: room.current_round + 1,
-            max_rounds: room.num_rounds,
+            // Elimination games don't have a fixed number of rounds
+            max_rounds: (room.game_mode != GameMode::Elimination).then_some(room.num_rounds),
+            target_score: if room.target_score == 0 { None } else { Some(room.target_score) },
             payload: scoreboard_entries,
+            teams: scoreboard_teams,
         });
 
-        // Advance round, stop if this was the last round
+        // Advance round, stop if this was the last round or someone won already
         room.current_round += 1;
-        if room.current_round == room.num_rounds {
-            room.send_all(&SendEvent::GameEnded);
-            for player in &mut room.players {
-                player.loaded = false;
-                player.guessed = None;
-                player.streak = 0;
-                player.points = 0;
+        let target_score_winner = target_score_winner(room);
+        let survivors = room
+            .players
+            .iter()
+            .filter(|p| !p.eliminated && !p.spectator)
+            .map(|p| p.name.clone())
+            .collect::<Vec<_>>();
+        // Elimination games go on until one player is left, unless nobody drops out for a while
+        let game_over = target_score_winner.is_some()
+            || match room.game_mode {
+                GameMode::Elimination => {
+                    survivors.len() <= 1
+                        || room.rounds_without_elimination >= MAX_ROUNDS_WITHOUT_ELIMINATION
+                }
+                _ => room.current_round == room.num_rounds,
+            };
+        if game_over {
+            if let Some(winner) = target_score_winner {
+                room.send_all(&SendEvent::Notify {
+                    message: format!("{} reached {} points and wins!", winner, room.target_score),
+                });
+            } else if room.game_mode == GameMode::Elimination {
+                let message = match &survivors[..] {
+                    [] => None,
+                    [winner] => Some(format!("{} wins!", winner)),
+                    survivors => Some(format!("{} survived until the end!", survivors.join(", "))),
+                };
+                if let Some(message) = message {
+                    room.send_all(&SendEvent::Notify { message });
+                }
             }
-            room.current_round = 0;
-            room.current_song = None;
-            room.round_start_time = None;
-            room.state = RoomState::Lobby;
+            end_game(room);
             return;
         }
 
-        room.song_provider.clone()
+        (room.song_provider.clone(), AudioModifier::pick(&room.audio_modifiers))
     };
-    let new_song = song_provider.next().await;
+    let next = next_song(&song_provider, modifier).await;
     {
         let mut room = room.lock();
+        // The game may have been stopped in the meantime
+        if room.state != RoomState::RoundEnded {
+            return;
+        }
+        let Some((new_song, modifier)) = next else {
+            end_game_without_songs(&mut room);
+            return;
+        };
 
         // Reset fields for next round
         room.current_song = Some(new_song);
+        room.current_modifier = modifier;
         for p in &mut room.players {
-            p.guessed = None;
-            p.loaded = false;
+            p.reset_round();
         }
+        room.choices.clear();
+        room.snippets.clear();
+        room.round_start_time = None;
 
         // Set in waiting mode to start the game once everyone loaded the song
         room.send_all(&SendEvent::NewTurn);
@@ -67,17 +199,167 @@ async fn finalize_round_and_kick_off_next_maybe(room: &parking_lot::Mutex<Room>)
     }
 }
 
+/// Returns the name of the team or player with the most points, if they reached the target score
+fn target_score_winner(room: &Room) -> Option<String> {
+    let scores: Vec<_> = if room.team_mode == TeamMode::Off {
+        room.players.iter().filter(|p| !p.spectator).map(|p| (&*p.name, p.points)).collect()
+    } else {
+        room.teams.iter().map(|t| (&*t.name, t.points)).collect()
+    };
+    leader_at_target_score(&scores, room.target_score)
+}
+
+/// The name with the most points, if it reached the target score. A target of zero is disabled
+fn leader_at_target_score(scores: &[(&str, u32)], target_score: u32) -> Option<String> {
+    if target_score == 0 {
+        return None;
+    }
+    let &(name, points) = scores.iter().max_by_key(|&&(_, points)| points)?;
+    (points >= target_score).then(|| name.to_string())
+}
+
+#[cfg(test)]
+#[test]
+fn test_leader_at_target_score() {
+    assert_eq!(leader_at_target_score(&[("a", 900), ("b", 1100)], 1000).as_deref(), Some("b"));
+    assert_eq!(leader_at_target_score(&[("a", 900), ("b", 1000)], 1000).as_deref(), Some("b"));
+    assert_eq!(leader_at_target_score(&[("a", 900), ("b", 999)], 1000), None);
+    assert_eq!(leader_at_target_score(&[("a", 900)], 0), None);
+    assert_eq!(leader_at_target_score(&[], 1000), None);
+}
+
+/// Elimination games end if nobody was eliminated for this many rounds in a row
+const MAX_ROUNDS_WITHOUT_ELIMINATION: u32 = 5;
+
+/// Everyone who didn't guess, or the slowest guesser if everyone guessed, given the remaining
+/// players and the points they got this round
+///
+/// Nobody is eliminated if that would eliminate all remaining players.
+fn players_to_eliminate(alive: &[(PlayerId, Option<u32>)]) -> Vec<PlayerId> {
+    let to_eliminate: Vec<_> = if alive.iter().any(|&(_, guessed)| guessed.is_none()) {
+        alive.iter().filter(|&&(_, guessed)| guessed.is_none()).map(|&(id, _)| id).collect()
+    } else {
+        // Points go down the later you guess, so the lowest points are the slowest guess
+        let slowest_points = alive.iter().filter_map(|&(_, guessed)| guessed).min();
+        alive.iter().filter(|&&(_, guessed)| guessed == slowest_points).map(|&(id, _)| id).collect()
+    };
+    if to_eliminate.len() == alive.len() {
+        return Vec::new();
+    }
+    to_eliminate
+}
+
+#[cfg(test)]
+#[test]
+fn test_players_to_eliminate() {
+    let (a, b, c) = (PlayerId(1), PlayerId(2), PlayerId(3));
+    assert_eq!(players_to_eliminate(&[(a, Some(200)), (b, None), (c, None)]), [b, c]);
+    assert_eq!(players_to_eliminate(&[(a, Some(200)), (b, Some(150)), (c, Some(150))]), [b, c]);
+    assert_eq!(players_to_eliminate(&[(a, None), (b, None)]), []);
+    assert_eq!(players_to_eliminate(&[(a, Some(200)), (b, Some(200))]), []);
+    assert_eq!(players_to_eliminate(&[]), []);
+}
+
+/// Eliminates the players that [`players_to_eliminate`] picks. Returns whether anyone was
+/// eliminated
+fn eliminate_players(room: &mut Room) -> bool {
+    let alive = room
+        .players
+        .iter()
+        .filter(|p| !p.eliminated && !p.spectator)
+        .map(|p| (p.id, p.guessed))
+        .collect::<Vec<_>>();
+    let to_eliminate = players_to_eliminate(&alive);
+
+    for player in room.players.iter_mut().filter(|p| to_eliminate.contains(&p.id)) {
+        player.eliminated = true;
+    }
+    for player in room.players.iter().filter(|p| to_eliminate.contains(&p.id)) {
+        room.send_all(&SendEvent::Notify { message: format!("{} was eliminated", player.name) });
+    }
+    !to_eliminate.is_empty()
+}
+
+fn start_round_if_everyone_loaded(
+    room_arc: &std::sync::Arc<parking_lot::Mutex<Room>>,
+    room: &mut Room,
+) {
+    if room.players.iter().all(|p| p.loaded || p.spectator)
+        && room.state == RoomState::WaitingForLoaded
+    {
+        let room_arc = room_arc.clone();
+        room.round_task = Some(spawn_attached(async move { play_round(&room_arc).await }));
+        room.state = RoomState::RoundStarted;
+    }
+}
+
+/// After the game was started, everyone reloads the page. Once all are back, load the first song
+async fn load_first_song_if_everyone_connected(room: &parking_lot::Mutex<Room>) {
+    let (song_provider, modifier) = {
+        let room = room.lock();
+
+        let everyone_connected = room.players.iter().all(|p| p.spectator || p.ws.lock().is_some());
+        if room.state != RoomState::WaitingForReconnect || !everyone_connected {
+            return;
+        }
+        (room.song_provider.clone(), AudioModifier::pick(&room.audio_modifiers))
+    };
+
+    let next = next_song(&song_provider, modifier).await;
+
+    let mut room = room.lock();
+    // Someone else may have been quicker
+    if room.state != RoomState::WaitingForReconnect {
+        return;
+    }
+    let Some((song, modifier)) = next else {
+        end_game_without_songs(&mut room);
+        return;
+    };
+    room.current_song = Some(song);
+    room.current_modifier = modifier;
+    room.send_all(&SendEvent::NewTurn);
+    room.send_all(&SendEvent::Loading);
+
+    room.state = RoomState::WaitingForLoaded;
+}
+
 async fn play_round(room: &parking_lot::Mutex<Room>) {
-    let (round_time, mut hints) = {
+    let (round_time, mut hints, game_mode, song_provider, title) = {
         let room = room.lock();
 
         room.send_all(&room.player_state_msg());
+        if let Some(modifier) = room.current_modifier {
+            room.send_all(&SendEvent::Notify {
+                message: format!("This round's song is {}!", modifier.name()),
+            });
+        }
+        if room.game_mode == GameMode::ReleaseYear && !guessing_release_year(&room) {
+            room.send_all(&SendEvent::Notify {
+                message: "This song's release year is unknown, guess its title instead!".into(),
+            });
+        }
+        let title = room.current_song.as_ref().unwrap().title.clone();
         (
             room.round_time_secs,
-            Hints::new(&room.current_song.as_ref().unwrap().title, room.round_time_secs),
+            Hints::new(&title, room.round_time_secs),
+            room.game_mode,
+            room.song_provider.clone(),
+            title,
         )
     };
 
+    if game_mode == GameMode::MultipleChoice {
+        let mut choices = song_provider.random_titles(3, &title).await;
+        choices.push(title.clone());
+        fastrand::shuffle(&mut choices);
+
+        let mut room = room.lock();
+        room.correct_choice = choices.iter().position(|c| *c == title).expect("we just pushed");
+        room.send_all(&SendEvent::MultipleChoice { options: choices.clone() });
+        room.choices = choices;
+    }
+
     // Client music playback borks itself without this for some reason
     tokio::time::sleep(std::time::Duration::from_millis(4000)).await;
 
@@ -87,14 +369,23 @@ async fn play_round(room: &parking_lot::Mutex<Room>) {
         let mut room = room.lock();
 
         // Go straight to next round if everyone guessed
-        if room.players.iter().all(|p| p.guessed.is_some() && p.ws.lock().is_some()) {
+        if room
+            .players
+            .iter()
+            .all(|p| p.spectator || p.eliminated || (p.done_guessing() && p.ws.lock().is_some()))
+        {
             break;
         }
 
         room.send_all(&SendEvent::Timer {
             message: timer,
             hint: hints.hint_at(timer),
-            scores: room.players.iter().map(|p| p.to_player_data()).collect(),
+            scores: room
+                .players
+                .iter()
+                .filter(|p| !p.spectator)
+                .map(|p| p.to_player_data())
+                .collect(),
             round_time,
         });
 
@@ -107,72 +398,182 @@ async fn play_round(room: &parking_lot::Mutex<Room>) {
     finalize_round_and_kick_off_next_maybe(room).await;
 }
 
-fn title_matches(title: &str, input: &str) -> bool {
-    fn strip_punctuation_and_make_lowercase(s: &str) -> String {
-        s.chars().filter(|c| c.is_alphabetic()).flat_map(|c| c.to_lowercase()).collect()
+/// Returns a notice for the player if they're not allowed to guess right now
+fn guess_rate_limit(room: &Room, player: &Player) -> Option<String> {
+    if room.max_guesses_per_round != 0 && player.guesses_this_round >= room.max_guesses_per_round {
+        return Some(format!(
+            "You've used up all {} guesses for this round",
+            room.max_guesses_per_round
+        ));
     }
-    let title = strip_punctuation_and_make_lowercase(title);
-    let input = strip_punctuation_and_make_lowercase(input);
-    levenshtein::levenshtein(&title, &input) <= title.len() / 10
+    if let Some(last_guess_time) = player.last_guess_time {
+        let cooldown = std::time::Duration::from_millis(room.guess_cooldown_ms as u64);
+        if last_guess_time.elapsed() < cooldown {
+            return Some("You're guessing too fast, slow down a bit".into());
+        }
+    }
+    None
 }
 
 fn points_for_guessing_now(room: &Room) -> u32 {
     let guess_time = (std::time::Instant::now() - room.round_start_time.unwrap()).as_secs_f32();
     let how_many_others_have_already_guessed =
-        room.players.iter().filter(|p| p.guessed.is_some()).count();
-    let hints_left = ((room.round_time_secs as f32 - guess_time) / 10.0) as u32;
-
-    // This is the original GuessTheSong algorithm as posted by "Frank (11studios)"
-    // in the GuessTheSong.io Discord server
-    // https://discord.com/channels/741670496822886470/741670497304969232/1092483679261053078
-    let mut points = 100;
-    match guess_time {
-        x if x < 10.0 => points += 125,
-        x if x < 20.0 => points += 100,
-        x if x < 25.0 => points += 75,
-        x if x < 45.0 => points += 62,
-        x if x < 70.0 => points += 50,
-        _ => points += 25,
+        room.players.iter().filter(|p| p.guessed.is_some() && !p.guessed_by_teammate).count();
+    points_for_guess(guess_time, room.round_time_secs, how_many_others_have_already_guessed)
+}
+
+/// In release year mode, guesses that are this many years off or more give no points
+const MAX_YEARS_OFF: u32 = 10;
+
+/// Whether players guess the release year this round. In release year mode, songs without a known
+/// release year fall back to guessing the title
+fn guessing_release_year(room: &Room) -> bool {
+    room.game_mode == GameMode::ReleaseYear
+        && room.current_song.as_ref().is_some_and(|song| song.release_year.is_some())
+}
+
+fn years_off(room: &Room, player: &Player) -> Option<u32> {
+    let release_year = room.current_song.as_ref()?.release_year?;
+    Some(player.year_guess?.abs_diff(release_year))
+}
+
+/// Marks the player as having guessed correctly, including streak bonus and team effects
+fn award_correct_guess(room: &mut Room, player_id: PlayerId) {
+    let mut points = points_for_guessing_now(room);
+    let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+    match room.game_mode {
+        GameMode::Themes | GameMode::MultipleChoice | GameMode::Elimination => {}
+        GameMode::ReleaseYear if !guessing_release_year(room) => {}
+        GameMode::Heardle => {
+            // The less of the song you needed to hear, the more points
+            let num_snippets = HEARDLE_SNIPPET_SECS.len() as u32;
+            points = points * (num_snippets - player.snippet as u32) / num_snippets;
+        }
+        GameMode::ReleaseYear => {
+            // The closer the guess, the more points
+            let years_off = years_off(room, player).unwrap_or(MAX_YEARS_OFF);
+            points = points * MAX_YEARS_OFF.saturating_sub(years_off) / MAX_YEARS_OFF;
+        }
     }
-    match how_many_others_have_already_guessed {
-        0 => points += 200,
-        1 => points += 150,
-        2 => points += 100,
-        _ => {}
+
+    let player = room.players.iter_mut().find(|p| p.id == player_id).unwrap();
+    player.guessed = Some(points);
+    player.streak_bonus =
+        u32::min(player.streak.saturating_mul(room.streak_bonus), room.max_streak_bonus);
+
+    // The whole team is done guessing
+    if let (TeamMode::SharedGuess, Some(team)) = (room.team_mode, player.team.clone()) {
+        for teammate in &mut room.players {
+            if teammate.team.as_ref() == Some(&team) && teammate.guessed.is_none() {
+                // The points count for the team through the guesser, the streak only for them
+                teammate.guessed = Some(0);
+                teammate.guessed_by_teammate = true;
+            }
+        }
     }
-    points += u32::min(hints_left * 25, 100);
 
-    points
+    // Make the user icon light up green
+    room.send_all(&room.player_state_msg());
 }
 
+/// Same as the `maxlength` of the team name input in the lobby
+const MAX_TEAM_NAME_LEN: usize = 30;
+
 async fn websocket_event(
     room_arc: &std::sync::Arc<parking_lot::Mutex<Room>>,
     player_id: PlayerId,
     event: ReceiveEvent,
 ) {
+    let host_only = matches!(
+        event,
+        ReceiveEvent::StartGame
+            | ReceiveEvent::StopGame
+            | ReceiveEvent::KickPlayer { .. }
+            | ReceiveEvent::BanPlayer { .. }
+    );
+    if host_only {
+        let room = room_arc.lock();
+        if !room.is_host(player_id) {
+            let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+            player.send(&SendEvent::Notify { message: "Only the host can do that".into() });
+            return;
+        }
+    }
+
     match event {
         ReceiveEvent::IncomingMsg { msg } => {
             let mut room = room_arc.lock();
+            let room = &mut *room; // Allow borrowing different fields mutably at the same time
 
-            if let Some(current_song) = &room.current_song {
-                if title_matches(&current_song.title, &msg) {
-                    room.players.iter_mut().find(|p| p.id == player_id).unwrap().guessed =
-                        Some(points_for_guessing_now(&room));
+            let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+            let year_guess = msg.trim().parse::<u32>().ok();
+            let guessing_release_year = guessing_release_year(room);
+            let is_guess = room.state == RoomState::RoundStarted
+                && room.round_start_time.is_some()
+                && !player.done_guessing()
+                && !player.eliminated
+                && !player.spectator
+                && match room.game_mode {
+                    GameMode::Themes | GameMode::Heardle | GameMode::Elimination => true,
+                    GameMode::ReleaseYear => !guessing_release_year || year_guess.is_some(),
+                    GameMode::MultipleChoice => false,
+                };
+            if is_guess {
+                if let Some(notice) = guess_rate_limit(room, player) {
+                    player.send(&SendEvent::Notify { message: notice });
+                    return;
+                }
 
-                    // Make the user icon light up green
+                let player = room.players.iter_mut().find(|p| p.id == player_id).unwrap();
+                player.guesses_this_round += 1;
+                player.last_guess_time = Some(std::time::Instant::now());
+
+                let correct = if guessing_release_year {
+                    // Only one try, and the guess mustn't show up in chat for others to see
+                    player.year_guess = year_guess;
+                    player.send(&SendEvent::Notify {
+                        message: format!("You guessed {}", year_guess.expect("checked above")),
+                    });
+                    let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+                    years_off(room, player).is_some_and(|years_off| years_off < MAX_YEARS_OFF)
+                } else {
+                    title_matches(&room.current_song.as_ref().unwrap().title, &msg)
+                };
+                if correct {
+                    award_correct_guess(room, player_id);
+                    return;
+                }
+                room.players.iter_mut().find(|p| p.id == player_id).unwrap().penalty +=
+                    room.wrong_guess_penalty;
+                if guessing_release_year {
                     room.send_all(&room.player_state_msg());
-
                     return;
                 }
             }
 
             let player = room.players.iter().find(|p| p.id == player_id).unwrap();
-            room.send_all(&SendEvent::Chat {
-                r#type: "message".into(),
+            if player.spectator && !room.spectator_chat {
+                return;
+            }
+            let chat_msg = SendEvent::Chat {
+                r#type: if player.spectator { "spectator" } else { "message" }.into(),
                 uuid: player.id,
                 username: player.name.clone(),
                 msg,
-            });
+            };
+            if player.spectator {
+                // Spectators may be watching a stream and know the answers
+                for other in room.players.iter().filter(|p| p.spectator) {
+                    other.send(&chat_msg);
+                }
+            } else if player.eliminated {
+                // Eliminated players mustn't help the remaining players
+                for other in room.players.iter().filter(|p| p.eliminated) {
+                    other.send(&chat_msg);
+                }
+            } else {
+                room.send_all(&chat_msg);
+            }
         }
         ReceiveEvent::StartGame => {
             let mut room = room_arc.lock();
@@ -186,11 +587,7 @@ async fn websocket_event(
 
             room.players.iter_mut().find(|p| p.id == player_id).unwrap().loaded = true;
             // room.send_all(&room.player_state_msg());
-            if room.players.iter().all(|p| p.loaded) && room.state == RoomState::WaitingForLoaded {
-                let room_arc = room_arc.clone();
-                room.round_task = Some(spawn_attached(async move { play_round(&room_arc).await }));
-                room.state = RoomState::RoundStarted;
-            }
+            start_round_if_everyone_loaded(room_arc, &mut room);
         }
         ReceiveEvent::TypingStatus { typing } => {
             let room = room_arc.lock();
@@ -198,8 +595,44 @@ async fn websocket_event(
             room.send_all(&SendEvent::PlayerTyping { uuid: player_id, typing });
         }
         ReceiveEvent::SkipRound => {
-            room_arc.lock().round_task = None; // aborts round task
-            finalize_round_and_kick_off_next_maybe(&room_arc).await;
+            {
+                let mut room = room_arc.lock();
+
+                if room.state != RoomState::RoundStarted {
+                    return;
+                }
+                // Hosts skip right away, everyone else votes
+                if !room.is_host(player_id) {
+                    let player = room.players.iter_mut().find(|p| p.id == player_id).unwrap();
+                    if player.voted_skip || player.spectator {
+                        return;
+                    }
+                    player.voted_skip = true;
+
+                    let connected = room
+                        .players
+                        .iter()
+                        .filter(|p| !p.spectator && p.ws.lock().is_some())
+                        .count();
+                    let votes = room.players.iter().filter(|p| p.voted_skip).count();
+                    // More than the configured share, but never more than everyone
+                    let needed =
+                        (connected * room.skip_vote_percent as usize / 100 + 1).min(connected);
+                    let name = &room.players.iter().find(|p| p.id == player_id).unwrap().name;
+                    room.send_all(&SendEvent::Notify {
+                        message: format!(
+                            "{} voted to skip this round ({}/{})",
+                            name, votes, needed
+                        ),
+                    });
+                    if votes < needed {
+                        return;
+                    }
+                }
+
+                room.round_task = None; // aborts round task
+            }
+            finalize_round_and_kick_off_next_maybe(room_arc).await;
         }
         ReceiveEvent::StopGame => {
             let mut room = room_arc.lock();
@@ -220,9 +653,182 @@ async fn websocket_event(
             };
             room.send_all(&SendEvent::EmoteReaction { uuid: player_id, reaction })
         }
+        ReceiveEvent::ChooseOption { index } => {
+            let mut room = room_arc.lock();
+            let room = &mut *room;
+
+            let player = room.players.iter_mut().find(|p| p.id == player_id).unwrap();
+            if room.game_mode != GameMode::MultipleChoice
+                || room.state != RoomState::RoundStarted
+                || room.round_start_time.is_none()
+                || player.guessed.is_some()
+                || player.chosen.is_some()
+                || player.spectator
+            {
+                return;
+            }
+
+            player.chosen = Some(index);
+            if index == room.correct_choice {
+                award_correct_guess(room, player_id);
+            } else {
+                player.penalty += room.wrong_guess_penalty;
+            }
+        }
+        ReceiveEvent::NextSnippet => {
+            let mut room = room_arc.lock();
+            let room = &mut *room;
+
+            let player = room.players.iter_mut().find(|p| p.id == player_id).unwrap();
+            if room.game_mode != GameMode::Heardle
+                || room.state != RoomState::RoundStarted
+                || player.guessed.is_some()
+                || player.spectator
+                || player.snippet + 1 >= HEARDLE_SNIPPET_SECS.len()
+            {
+                return;
+            }
+
+            player.snippet += 1;
+            player.send(&SendEvent::SnippetUnlocked {
+                seconds: HEARDLE_SNIPPET_SECS[player.snippet],
+            });
+        }
+        ReceiveEvent::JoinTeam { team } => {
+            let mut room = room_arc.lock();
+
+            let team = team.trim();
+            let spectator = room.players.iter().find(|p| p.id == player_id).unwrap().spectator;
+            if room.team_mode == TeamMode::Off
+                || room.state != RoomState::Lobby
+                || team.is_empty()
+                || team.chars().count() > MAX_TEAM_NAME_LEN
+                || spectator
+            {
+                return;
+            }
+
+            if !room.teams.iter().any(|t| t.name == team) {
+                room.teams.push(Team { name: team.to_string(), points: 0, point_diff: 0 });
+            }
+            room.players.iter_mut().find(|p| p.id == player_id).unwrap().team =
+                Some(team.to_string());
+            room.remove_empty_teams();
+
+            room.send_all(&room.player_state_msg());
+            room.send_all(&room.team_state_msg());
+        }
+        ReceiveEvent::LeaveTeam => {
+            let mut room = room_arc.lock();
+
+            if room.state != RoomState::Lobby {
+                return;
+            }
+
+            room.players.iter_mut().find(|p| p.id == player_id).unwrap().team = None;
+            room.remove_empty_teams();
+
+            room.send_all(&room.player_state_msg());
+            room.send_all(&room.team_state_msg());
+        }
+        ReceiveEvent::GrantCoHost { uuid } | ReceiveEvent::RevokeCoHost { uuid } => {
+            let mut room = room_arc.lock();
+
+            if room.owner != Some(player_id) {
+                let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+                player.send(&SendEvent::Notify {
+                    message: "Only the room owner can change co-hosts".into(),
+                });
+                return;
+            }
+
+            let co_host = matches!(event, ReceiveEvent::GrantCoHost { .. });
+            if let Some(target) = room.players.iter_mut().find(|p| p.id == uuid) {
+                target.co_host = co_host;
+                room.send_all(&room.player_state_msg());
+            }
+        }
+        ReceiveEvent::TransferHost { uuid } => {
+            let mut room = room_arc.lock();
+
+            if room.owner != Some(player_id) {
+                let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+                player.send(&SendEvent::Notify {
+                    message: "Only the room owner can hand over the room".into(),
+                });
+                return;
+            }
+
+            if let Some(new_owner) = room.players.iter().find(|p| p.id == uuid) {
+                let message = format!("{} is now the host", new_owner.name);
+                room.owner = Some(uuid);
+                room.send_all(&SendEvent::Notify { message });
+                room.send_all(&room.player_state_msg());
+            }
+        }
+        ReceiveEvent::KickPlayer { uuid } | ReceiveEvent::BanPlayer { uuid } => {
+            {
+                let mut room = room_arc.lock();
+
+                if uuid == player_id || room.owner == Some(uuid) {
+                    let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+                    player.send(&SendEvent::Notify {
+                        message: "You can't remove yourself or the room owner".into(),
+                    });
+                    return;
+                }
+                let Some(index) = room.players.iter().position(|p| p.id == uuid) else {
+                    return;
+                };
+
+                let ban = matches!(event, ReceiveEvent::BanPlayer { .. });
+                let removed = room.players.remove(index);
+                if ban {
+                    room.banned_tokens.insert(removed.token.clone());
+                    room.banned_ips.insert(removed.ip);
+                }
+                if let Some(ws) = &*removed.ws.lock() {
+                    ws.close(
+                        REMOVED_CLOSE_CODE,
+                        if ban {
+                            "You were banned from this room"
+                        } else {
+                            "You were kicked from this room"
+                        },
+                    );
+                }
+                room.remove_empty_teams();
+                if room.state == RoomState::Lobby {
+                    room.admit_queued_players();
+                }
+
+                let host_name =
+                    room.players.iter().find(|p| p.id == player_id).unwrap().name.clone();
+                room.send_all(&SendEvent::Notify {
+                    message: format!(
+                        "{} was {} by {}",
+                        removed.name,
+                        if ban { "banned" } else { "kicked" },
+                        host_name
+                    ),
+                });
+                room.send_all(&room.player_state_msg());
+                if room.team_mode != TeamMode::Off {
+                    room.send_all(&room.team_state_msg());
+                }
+                // The removed player may have been the one everyone was waiting for
+                start_round_if_everyone_loaded(room_arc, &mut room);
+            }
+            load_first_song_if_everyone_connected(room_arc).await;
+        }
     }
 }
 
+/// WebSocket close code for kicked and banned players, from the range reserved for applications
+const REMOVED_CLOSE_CODE: u16 = 4000;
+
+const HOST_RECONNECT_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
+
 pub async fn websocket_connect(
     room_arc: std::sync::Arc<parking_lot::Mutex<Room>>,
     player_id: PlayerId,
@@ -235,6 +841,12 @@ pub async fn websocket_connect(
 
         if let Some(player) = room.players.iter().find(|p| p.id == player_id) {
             *player.ws.lock() = Some(ws.clone());
+            if player.queued {
+                player.send(&SendEvent::Notify {
+                    message: "The room is full, you'll join at the next round with a free spot"
+                        .into(),
+                });
+            }
         } else {
             log::warn!("no player with ID {} has joined!", player_id.0);
             return;
@@ -247,43 +859,36 @@ pub async fn websocket_connect(
 
             // Notify newly joined player about all existing players
             let new_player = room.players.iter().find(|p| p.id == player_id).unwrap();
-            for player in &room.players {
+            for player in room.players.iter().filter(|p| !p.spectator) {
                 new_player.send(&SendEvent::Join {
                     message: player.name.clone(),
                     payload: Box::new(room.player_state_msg()),
                 });
             }
-        }
-        RoomState::WaitingForReconnect => {
-            let (everyone_connected, song_provider) = {
-                let room = room_arc.lock();
-
-                (room.players.iter().all(|p| p.ws.lock().is_some()), room.song_provider.clone())
-            };
-
-            if everyone_connected {
-                let song = song_provider.next().await;
-
-                let mut room = room_arc.lock();
-
-                room.current_song = Some(song);
-                room.send_all(&SendEvent::NewTurn);
-                room.send_all(&SendEvent::Loading);
-
-                room.state = RoomState::WaitingForLoaded;
+            if room.team_mode != TeamMode::Off {
+                new_player.send(&room.team_state_msg());
             }
         }
-        RoomState::RoundStarted | RoomState::WaitingForLoaded => {
+        RoomState::WaitingForReconnect => load_first_song_if_everyone_connected(&room_arc).await,
+        RoomState::RoundStarted | RoomState::RoundEnded | RoomState::WaitingForLoaded => {
             let mut room = room_arc.lock();
 
             room.send_all(&room.player_state_msg());
             room.send_all(&SendEvent::ResumeAudio);
+            if !room.choices.is_empty() {
+                let player = room.players.iter().find(|p| p.id == player_id).unwrap();
+                player.send(&SendEvent::MultipleChoice { options: room.choices.clone() });
+            }
             // Due to ResumeAudio, the client won't send a loaded confirmation
             room.players.iter_mut().find(|p| p.id == player_id).unwrap().loaded = true;
         }
     }
 
     while let Ok(event) = ws.recv::<ReceiveEvent>().await {
+        // Kicked players stay connected until their client acknowledges the close frame
+        if !room_arc.lock().players.iter().any(|p| p.id == player_id) {
+            break;
+        }
         websocket_event(&room_arc, player_id, event).await;
     }
 
@@ -295,4 +900,22 @@ pub async fn websocket_connect(
             *player.ws.lock() = None;
         }
     }
+
+    // Pages reload between lobby and game, so give the owner some time to reconnect
+    tokio::time::sleep(HOST_RECONNECT_GRACE_PERIOD).await;
+    let mut room = room_arc.lock();
+    let left = room.players.iter().any(|p| p.id == player_id && p.ws.lock().is_none());
+    if left && room.state == RoomState::Lobby {
+        // Free up the spot for the waiting list. During a game, this happens when the round ends
+        room.players.retain(|p| p.id != player_id);
+        room.remove_empty_teams();
+        room.hand_over_ownership_if_needed();
+        room.admit_queued_players();
+        room.send_all(&room.player_state_msg());
+        if room.team_mode != TeamMode::Off {
+            room.send_all(&room.team_state_msg());
+        }
+    } else if room.hand_over_ownership_if_needed() {
+        room.send_all(&room.player_state_msg());
+    }
 }
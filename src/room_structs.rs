@@ -1,7 +1,21 @@
+use crate::ffmpeg::*;
 use crate::song_provider::*;
 use crate::utils::*;
 use crate::ws_structs::*;
 
+/// Secret that identifies a player's browser via the `user` cookie. Unlike [`PlayerId`], this is
+/// never sent to other players, so it can't be used to impersonate someone
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SessionToken(pub String);
+
+impl SessionToken {
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes).expect("OS random number generator failed");
+        Self(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
 #[derive(Debug)]
 pub struct Player {
     /// This must be an Arc to clone it out and avoid locking the room data while waiting for a
@@ -9,11 +23,29 @@ pub struct Player {
     pub ws: parking_lot::Mutex<Option<std::sync::Arc<WebSocket>>>,
     pub name: String,
     pub id: PlayerId,
+    pub token: SessionToken,
+    pub ip: std::net::IpAddr,
     pub loaded: bool,
     pub guessed: Option<u32>, // Points gained
+    /// Done guessing because a teammate guessed, in shared guess team mode. Doesn't count
+    /// towards the streak
+    pub guessed_by_teammate: bool,
+    pub streak_bonus: u32, // Points gained on top of `guessed` due to streak
     pub streak: u32,
     pub points: u32,
+    pub guesses_this_round: u32,
+    pub last_guess_time: Option<std::time::Instant>,
+    pub penalty: u32,          // Points lost this round due to wrong guesses
+    pub chosen: Option<usize>, // Multiple-choice answer of this round
+    pub snippet: usize,        // Index into HEARDLE_SNIPPET_SECS
+    pub year_guess: Option<u32>,
+    pub eliminated: bool, // Only in elimination mode. Eliminated players are spectators
     pub emoji: String,
+    pub team: Option<String>,
+    pub voted_skip: bool,
+    pub spectator: bool, // Watches without playing, not shown in the player list
+    pub queued: bool,    // Spectating because the room was full, joins at the next round
+    pub co_host: bool,   // Granted by the owner, allows the same room controls as the owner
 }
 
 impl Player {
@@ -29,12 +61,41 @@ impl Player {
 }
 
 impl Player {
+    /// Clears everything related to the current round
+    pub fn reset_round(&mut self) {
+        self.loaded = false;
+        self.guessed = None;
+        self.guessed_by_teammate = false;
+        self.streak_bonus = 0;
+        self.guesses_this_round = 0;
+        self.penalty = 0;
+        self.chosen = None;
+        self.snippet = 0;
+        self.year_guess = None;
+        self.voted_skip = false;
+    }
+
+    /// Whether the player has used up their chance to guess this round
+    pub fn done_guessing(&self) -> bool {
+        self.guessed.is_some() || self.chosen.is_some() || self.year_guess.is_some()
+    }
+
+    /// Total points including what was gained and lost in the current round
+    pub fn points_after_round(&self) -> u32 {
+        self.points
+            .saturating_add(self.guessed.unwrap_or(0))
+            .saturating_add(self.streak_bonus)
+            .saturating_sub(self.penalty)
+    }
+
     pub fn to_scoreboard_player(&self) -> ScoreboardPlayer {
         ScoreboardPlayer {
             uuid: self.id,
             display_name: self.name.clone(),
             points: self.points,
             point_diff: self.guessed.unwrap_or(0),
+            streak_bonus: self.streak_bonus,
+            penalty: self.penalty,
             streak: self.streak,
         }
     }
@@ -43,58 +104,286 @@ impl Player {
         SinglePlayerData {
             uuid: self.id,
             username: self.name.clone(),
-            points: self.points + self.guessed.unwrap_or(0),
+            points: self.points_after_round(),
             prev_points: self.points,
             streak: self.streak,
             emoji: self.emoji.clone(),
             loaded: self.loaded,
             guessed: self.guessed.is_some(),
             disconnected: self.ws.lock().is_none(),
+            team: self.team.clone(),
+            eliminated: self.eliminated,
+            co_host: self.co_host,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, serde::Deserialize)]
+pub enum GameMode {
+    /// Guess the song title via chat
+    #[default]
+    Themes,
+    /// Pick the song title out of four options
+    MultipleChoice,
+    /// Guess the song title via chat, but the audio unlocks step by step
+    Heardle,
+    /// Guess the year the song was released via chat
+    ReleaseYear,
+    /// Guess the song title via chat, whoever didn't guess or guessed slowest is eliminated
+    Elimination,
+}
+
+impl GameMode {
+    pub const ALL: &'static [Self] =
+        &[Self::Themes, Self::MultipleChoice, Self::Heardle, Self::ReleaseYear, Self::Elimination];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Themes => "Themes",
+            GameMode::MultipleChoice => "Multiple Choice",
+            GameMode::Heardle => "Heardle",
+            GameMode::ReleaseYear => "Release Year",
+            GameMode::Elimination => "Elimination",
         }
     }
 }
 
+/// How much of the song each player can hear in Heardle mode, step by step
+pub const HEARDLE_SNIPPET_SECS: &[u32] = &[1, 2, 4, 7, 11, 16];
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamMode {
+    #[default]
+    Off,
+    /// The first correct guess counts for the whole team
+    SharedGuess,
+    /// Every team member guesses for themselves, team score is the sum
+    Individual,
+}
+
+pub struct Team {
+    pub name: String,
+    pub points: u32,
+    pub point_diff: u32,
+}
+
+impl Team {
+    pub fn to_scoreboard_team(&self) -> ScoreboardTeam {
+        ScoreboardTeam { name: self.name.clone(), points: self.points, point_diff: self.point_diff }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum RoomState {
     Lobby,
     WaitingForReconnect,
     WaitingForLoaded,
     RoundStarted,
+    /// The scoreboard is shown while the next song downloads
+    RoundEnded,
 }
 
 pub struct Room {
     // Static data
     pub name: String,
     pub password: Option<String>, // If None, room is public
-    // explicit_songs: bool,
+    pub unlisted: bool,           // Hidden from the server browser, only joinable via code
     pub num_rounds: u32,
+    /// Game ends early once a player (or team in team mode) has this many points. Zero disables
+    pub target_score: u32,
     pub round_time_secs: u32,
+    /// Bonus points per consecutive correct round before this one. Zero disables streak bonuses
+    pub streak_bonus: u32,
+    pub max_streak_bonus: u32,
+    /// Zero means unlimited
+    pub max_guesses_per_round: u32,
+    pub guess_cooldown_ms: u32,
+    pub wrong_guess_penalty: u32,
+    /// Non-host players skip the round once more than this share of connected players voted
+    pub skip_vote_percent: u32,
+    /// Zero means unlimited. Spectators don't count
+    pub max_players: u32,
+    /// If false, spectators can't chat at all. Their chat is only ever shown to other spectators
+    pub spectator_chat: bool,
+    pub game_mode: GameMode,
+    /// A random one of these is applied to every song, none if empty
+    pub audio_modifiers: Vec<AudioModifier>,
+    pub team_mode: TeamMode,
+    pub allow_explicit: bool,
     pub created_at: std::time::Instant,
 
     // Dynamic data, always present
     pub song_provider: std::sync::Arc<SongProvider>,
-    pub players: Vec<Player>,
+    pub players: Vec<Player>,    // In join order
+    pub owner: Option<PlayerId>, // Only None while the room is empty
+    pub banned_tokens: std::collections::HashSet<SessionToken>,
+    pub banned_ips: std::collections::HashSet<std::net::IpAddr>,
+    pub teams: Vec<Team>,
     pub state: RoomState,
     pub empty_last_time_we_checked: bool,
 
     // Dynamic data, only while playing
     pub current_round: u32, // zero-indexed
+    pub rounds_without_elimination: u32,
     pub round_task: Option<AttachedTask>,
     pub current_song: Option<Song>,
+    pub current_modifier: Option<AudioModifier>,
+    pub choices: Vec<String>, // Only in multiple-choice mode
+    pub correct_choice: usize,
+    /// Clipped audio by length in seconds. Cleared every round, so clips that are still being made
+    /// for the previous song never end up here
+    pub snippets: std::collections::HashMap<u32, std::sync::Arc<tokio::sync::OnceCell<Vec<u8>>>>,
     pub round_start_time: Option<std::time::Instant>,
 }
 
 impl Room {
+    pub fn player_by_token(&self, token: &SessionToken) -> Option<&Player> {
+        self.players.iter().find(|p| &p.token == token)
+    }
+
     pub fn send_all(&self, msg: &SendEvent) {
         for player in &self.players {
             player.send(msg);
         }
     }
 
+    /// Whether the player may start, skip and stop the game and change the room settings
+    pub fn is_host(&self, player_id: PlayerId) -> bool {
+        self.owner == Some(player_id) || self.players.iter().any(|p| p.id == player_id && p.co_host)
+    }
+
+    /// Passes ownership on to the longest-connected player if the owner left or disconnected.
+    /// Returns whether the owner changed
+    pub fn hand_over_ownership_if_needed(&mut self) -> bool {
+        let players = self
+            .players
+            .iter()
+            .map(|p| OwnerCandidate {
+                id: p.id,
+                spectator: p.spectator,
+                connected: p.ws.lock().is_some(),
+            })
+            .collect::<Vec<_>>();
+        let new_owner_id = choose_owner(self.owner, &players);
+        if new_owner_id == self.owner {
+            return false;
+        }
+        if let Some(new_owner) = self.players.iter().find(|p| Some(p.id) == new_owner_id) {
+            log::info!("Handing over room {:?} to {}", self.name, new_owner.name);
+            self.send_all(&SendEvent::Notify {
+                message: format!("{} is now the host", new_owner.name),
+            });
+        }
+        self.owner = new_owner_id;
+        true
+    }
+
+    pub fn num_playing(&self) -> usize {
+        self.players.iter().filter(|p| !p.spectator).count()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.max_players != 0 && self.num_playing() >= self.max_players as usize
+    }
+
+    /// Moves players from the waiting list into the game, in join order, while there's space
+    pub fn admit_queued_players(&mut self) {
+        let mut admitted = false;
+        while !self.is_full() {
+            let Some(player) = self.players.iter_mut().find(|p| p.queued) else {
+                break;
+            };
+            player.queued = false;
+            player.spectator = false;
+            let message = player.name.clone();
+            if self.owner.is_none() {
+                self.owner = Some(player.id);
+            }
+            self.send_all(&SendEvent::Join { message, payload: Box::new(self.player_state_msg()) });
+            admitted = true;
+        }
+        if admitted {
+            self.send_all(&self.player_state_msg());
+        }
+    }
+
     pub fn player_state_msg(&self) -> SendEvent {
         SendEvent::PlayerData {
-            payload: self.players.iter().map(|p| p.to_player_data()).collect(),
-            owner: self.players.first().unwrap().id,
+            payload: self
+                .players
+                .iter()
+                .filter(|p| !p.spectator)
+                .map(|p| p.to_player_data())
+                .collect(),
+            owner: self.owner,
         }
     }
+
+    pub fn remove_empty_teams(&mut self) {
+        let players = &self.players;
+        self.teams.retain(|t| players.iter().any(|p| p.team.as_ref() == Some(&t.name)));
+    }
+
+    pub fn team_state_msg(&self) -> SendEvent {
+        SendEvent::TeamData {
+            payload: self
+                .teams
+                .iter()
+                .map(|team| TeamData {
+                    name: team.name.clone(),
+                    points: team.points,
+                    members: self
+                        .players
+                        .iter()
+                        .filter(|p| p.team.as_ref() == Some(&team.name))
+                        .map(|p| p.id)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+struct OwnerCandidate {
+    id: PlayerId,
+    spectator: bool,
+    connected: bool,
+}
+
+/// Who should own the room, given the players in join order: the owner while they're connected,
+/// otherwise the longest-connected player. A disconnected owner keeps the room if there's nobody
+/// else
+fn choose_owner(owner: Option<PlayerId>, players: &[OwnerCandidate]) -> Option<PlayerId> {
+    let owner = owner.and_then(|owner| players.iter().find(|p| p.id == owner));
+    if owner.is_some_and(|owner| owner.connected) {
+        return owner.map(|owner| owner.id);
+    }
+
+    match players.iter().find(|p| !p.spectator && p.connected) {
+        Some(player) => Some(player.id),
+        None if owner.is_some() => owner.map(|owner| owner.id),
+        None => players.iter().find(|p| !p.spectator).map(|p| p.id),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_choose_owner() {
+    let player =
+        |id, spectator, connected| OwnerCandidate { id: PlayerId(id), spectator, connected };
+    let (a, b, c) = (Some(PlayerId(1)), Some(PlayerId(2)), Some(PlayerId(3)));
+
+    let everyone_connected = [player(1, false, true), player(2, false, true)];
+    assert_eq!(choose_owner(b, &everyone_connected), b);
+    let owner_disconnected =
+        [player(1, true, true), player(2, false, false), player(3, false, true)];
+    assert_eq!(choose_owner(b, &owner_disconnected), c);
+    let owner_left = [player(2, false, true), player(3, false, true)];
+    assert_eq!(choose_owner(a, &owner_left), b);
+    let nobody_connected = [player(1, false, false), player(2, false, false)];
+    assert_eq!(choose_owner(b, &nobody_connected), b);
+    assert_eq!(choose_owner(c, &nobody_connected), a);
+    assert_eq!(choose_owner(a, &[player(2, true, true)]), None);
+    assert_eq!(choose_owner(None, &[]), None);
 }
@@ -1,6 +1,11 @@
+use crate::daily::*;
+use crate::error::*;
+use crate::ffmpeg::*;
+use crate::practice::*;
 use crate::room_flow::*;
 use crate::room_structs::*;
 use crate::song_provider::*;
+use crate::themes::*;
 use crate::utils::*;
 use crate::ws_structs::*;
 
@@ -20,12 +25,33 @@ pub struct State {
         std::collections::HashMap<u32, std::sync::Arc<parking_lot::Mutex<Room>>>,
     >,
     spotify_client: std::sync::Arc<rspotify::ClientCredsSpotify>,
+    practice_sessions: parking_lot::Mutex<
+        std::collections::HashMap<u64, std::sync::Arc<parking_lot::Mutex<PracticeSession>>>,
+    >,
+    /// Keyed by user and playlist URL
+    personal_bests: parking_lot::Mutex<std::collections::HashMap<(String, String), u32>>,
+    daily_playlist: Option<String>,
+    daily: tokio::sync::Mutex<Option<DailyChallenge>>,
+    /// Held while the songs are downloaded lazily on the first request of the day, so that only
+    /// one request downloads them and `daily` stays available for the leaderboard meanwhile
+    daily_download: tokio::sync::Mutex<()>,
+    /// Wrong room passwords, by IP, to stop people from brute-forcing PINs
+    failed_joins: RateLimiter<std::net::IpAddr>,
+    themes: Vec<Theme>,
+}
+
+fn session_token(cookies: Option<&axum::headers::Cookie>) -> Option<SessionToken> {
+    cookies.and_then(|cookies| cookies.get("user")).map(|token| SessionToken(token.to_string()))
+}
+
+fn session_cookie(token: &SessionToken) -> String {
+    format!("user={}; Path=/; HttpOnly; SameSite=Lax", token.0)
 }
 
 fn gen_id() -> PlayerId {
     fn nanos_since_startup() -> u128 {
         use once_cell::sync::Lazy;
-        static START_TIME: Lazy<std::time::Instant> = Lazy::new(|| std::time::Instant::now());
+        static START_TIME: Lazy<std::time::Instant> = Lazy::new(std::time::Instant::now);
         (std::time::Instant::now() - *START_TIME).as_nanos()
     }
 
@@ -45,22 +71,30 @@ pub async fn get_server_browser_ws(
                     .rooms
                     .lock()
                     .iter()
-                    .map(|(&id, room)| {
+                    .filter_map(|(&id, room)| {
                         let room = room.lock();
+                        if room.unlisted {
+                            return None;
+                        }
 
-                        ListedRoom {
+                        Some(ListedRoom {
                             code: id,
                             idle: (std::time::Instant::now() - room.created_at).as_secs(),
                             name: room.name.clone(),
-                            players: room.players.len(),
+                            players: room.num_playing(),
+                            max_players: if room.max_players == 0 {
+                                None
+                            } else {
+                                Some(room.max_players)
+                            },
                             status: if room.password.is_some() {
                                 ListedRoomState::Private
                             } else {
                                 ListedRoomState::Public
                             },
                             theme: room.song_provider.playlist_name().into(),
-                            game_mode: "Themes".into(), // is this ever something else?
-                        }
+                            game_mode: room.game_mode.name().into(),
+                        })
                     })
                     .collect(),
             }) {
@@ -72,23 +106,47 @@ pub async fn get_server_browser_ws(
     })
 }
 
+#[derive(serde::Deserialize)]
+pub struct JoinQuery {
+    error: Option<String>,
+}
+
 pub async fn get_join(
     axum::extract::Path(room_id): axum::extract::Path<String>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
+    axum::extract::Query(query): axum::extract::Query<JoinQuery>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    // Only fixed messages are put into the page, never the raw query parameter
+    let error = match query.error.as_deref() {
+        Some("wrong_password") => "Wrong room password",
+        Some("too_many_attempts") => "Too many wrong passwords, please try again later",
+        Some("banned") => "You are banned from this room",
+        _ => "",
+    };
     Ok(axum::response::Html(
-        std::fs::read_to_string("frontend/join.html").unwrap().replace("ROOMID", &room_id),
+        std::fs::read_to_string("frontend/join.html")?
+            .replace("ROOMID", &room_id)
+            .replace("JOINERROR", error),
     ))
 }
 
+const MAX_FAILED_JOINS: usize = 5;
+const FAILED_JOINS_WINDOW: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
 #[derive(Debug, serde::Deserialize)]
 pub struct PostJoinForm {
     username: String,
     room_code: u32,
+    #[serde(default)]
+    password: String,
+    /// Checkbox, only sent when checked
+    #[serde(default)]
+    spectate: Option<String>,
 }
 
-fn emoji_from_cookies(cookies: &axum::headers::Cookie) -> &'static str {
+fn emoji_from_cookies(cookies: Option<&axum::headers::Cookie>) -> &'static str {
     let inner = || -> Result<&'static str, &'static str> {
         let emoji_index = cookies
+            .ok_or("no cookies")?
             .get("emoji")
             .ok_or("missing cookie")?
             .parse::<usize>()
@@ -99,52 +157,99 @@ fn emoji_from_cookies(cookies: &axum::headers::Cookie) -> &'static str {
         Ok(emoji) => emoji,
         Err(e) => {
             log::warn!("couldn't get emoji from cookie: {}", e);
-            &EMOJIS[0]
+            EMOJIS[0]
         }
     }
 }
 
 pub async fn post_join(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
-    axum::extract::Form(form): axum::extract::Form<PostJoinForm>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
-    log::info!("Room joined: {:?}", form);
+    axum::extract::ConnectInfo(address): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    form: Result<axum::extract::Form<PostJoinForm>, axum::extract::rejection::FormRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Form(form) = form?;
+    log::info!("Room joined: {} to room {}", form.username, form.room_code);
 
-    let PostJoinForm { username, room_code } = form;
+    let PostJoinForm { username, room_code, password, spectate } = form;
+    let mut spectate = spectate.is_some();
     let player_id = gen_id();
+    let token = SessionToken::generate();
     let room = state
         .rooms
         .lock()
         .get(&room_code)
-        .ok_or_else(|| axum::response::Redirect::to("/server-browser"))?
+        .ok_or_else(|| Error::redirect("/server-browser"))?
         .clone();
 
     let mut room = room.lock();
+    let ip = address.ip();
+    let banned_token =
+        session_token(cookies.as_deref()).is_some_and(|token| room.banned_tokens.contains(&token));
+    if banned_token || room.banned_ips.contains(&ip) {
+        log::info!("Rejecting join to room {} from {}: banned", room_code, ip);
+        return Err(Error::redirect(format!("/join/{}?error=banned", room_code)));
+    }
+    if let Some(room_password) = &room.password {
+        if state.failed_joins.is_limited(&ip) {
+            log::warn!("Rejecting join to room {} from {}: too many attempts", room_code, ip);
+            return Err(Error::redirect(format!("/join/{}?error=too_many_attempts", room_code)));
+        }
+        if *room_password != password {
+            state.failed_joins.record(ip);
+            return Err(Error::redirect(format!("/join/{}?error=wrong_password", room_code)));
+        }
+    }
+    let queued = !spectate && room.is_full();
+    spectate |= queued;
     room.players.push(Player {
         name: username.to_string(),
         id: player_id,
+        token: token.clone(),
+        ip: address.ip(),
         loaded: false,
         guessed: None,
+        guessed_by_teammate: false,
+        streak_bonus: 0,
         points: 0,
         streak: 0,
-        emoji: emoji_from_cookies(&cookies).to_string(),
+        guesses_this_round: 0,
+        last_guess_time: None,
+        penalty: 0,
+        chosen: None,
+        snippet: 0,
+        year_guess: None,
+        eliminated: false,
+        voted_skip: false,
+        spectator: spectate,
+        queued,
+        co_host: false,
+        team: None,
+        emoji: emoji_from_cookies(cookies.as_deref()).to_string(),
         ws: parking_lot::Mutex::new(None),
     });
     let player = room.players.last().expect("impossible, we just pushed");
+    if player.spectator {
+        // Queued players are told about the waiting list once connected
+        if !player.queued {
+            room.send_all(&SendEvent::Notify { message: format!("{} is spectating", player.name) });
+        }
+    } else {
+        if room.owner.is_none() {
+            room.owner = Some(player_id); // No other players in the room
+        }
+        let player = room.players.last().expect("impossible, we just pushed");
 
-    // Notify existing players about this newly joined user
-    room.send_all(&room.player_state_msg());
-    room.send_all(&SendEvent::Join {
-        message: player.name.clone(),
-        payload: Box::new(room.player_state_msg()),
-    });
+        // Notify existing players about this newly joined user
+        room.send_all(&room.player_state_msg());
+        room.send_all(&SendEvent::Join {
+            message: player.name.clone(),
+            payload: Box::new(room.player_state_msg()),
+        });
+    }
 
     Ok((
-        axum::response::AppendHeaders([(
-            axum::http::header::SET_COOKIE,
-            format!("user={}; Path=/", player_id.0),
-        )]),
+        axum::response::AppendHeaders([(axum::http::header::SET_COOKIE, session_cookie(&token))]),
         axum::response::Redirect::to(&format!("/room/{}", room_code)),
     ))
 }
@@ -153,58 +258,261 @@ pub async fn post_join(
 pub struct CreateRoomForm {
     username: String,
     room_name: String,
+    #[serde(default)]
+    theme: String,
+    #[serde(default)]
     playlist: String,
     password: String,
+    /// Checkbox, only sent when checked
+    #[serde(default)]
+    unlisted: Option<String>,
+    /// Checkbox, only sent when checked
+    #[serde(default)]
+    explicit: Option<String>,
     rounds: u32,
     round_time: u32,
+    #[serde(default)]
+    target_score: u32,
+    #[serde(default)]
+    streak_bonus: u32,
+    #[serde(default)]
+    max_streak_bonus: u32,
+    #[serde(default)]
+    max_guesses_per_round: u32,
+    #[serde(default)]
+    guess_cooldown_ms: u32,
+    #[serde(default)]
+    wrong_guess_penalty: u32,
+    #[serde(default = "default_skip_vote_percent")]
+    skip_vote_percent: u32,
+    #[serde(default)]
+    max_players: u32,
+    /// Checkbox, only sent when checked
+    #[serde(default)]
+    spectator_chat: Option<String>,
+    #[serde(default)]
+    game_mode: GameMode,
+    #[serde(default)]
+    team_mode: TeamMode,
+    #[serde(default, deserialize_with = "deserialize_audio_modifiers")]
+    audio_modifier: Vec<AudioModifier>,
+}
+
+/// Random 6-digit code, so that rooms can't be found by counting up
+fn gen_room_code<V>(rooms: &std::collections::HashMap<u32, V>) -> u32 {
+    loop {
+        let code = fastrand::u32(100_000..1_000_000);
+        if !rooms.contains_key(&code) {
+            return code;
+        }
+    }
+}
+
+fn default_skip_vote_percent() -> u32 {
+    50
+}
+
+/// The URL of the chosen theme, or else the custom playlist URL, which may be empty
+fn chosen_playlist_url<'a>(
+    themes: &'a [Theme],
+    theme: &str,
+    playlist: &'a str,
+) -> Result<&'a str, &'static str> {
+    if theme.is_empty() {
+        return Ok(playlist.trim());
+    }
+    find_theme(themes, theme).map(|theme| theme.url.as_str()).ok_or("Unknown theme")
+}
+
+const MAX_NAME_LEN: usize = 50;
+const MAX_ROUNDS: u32 = 50;
+const MIN_ROUND_TIME_SECS: u32 = 10;
+const MAX_ROUND_TIME_SECS: u32 = 300;
+const MAX_TARGET_SCORE: u32 = 100_000;
+const MAX_STREAK_BONUS: u32 = 500;
+const MAX_GUESSES_PER_ROUND: u32 = 100;
+const MAX_GUESS_COOLDOWN_MS: u32 = 10_000;
+const MAX_WRONG_GUESS_PENALTY: u32 = 500;
+const MAX_PLAYERS: u32 = 100;
+
+/// Checks the settings shared by room creation and the lobby, returns a message for the user
+fn validate_room_settings(room_name: &str, rounds: u32, round_time: u32) -> Result<(), String> {
+    let name_len = room_name.trim().chars().count();
+    if name_len == 0 || name_len > MAX_NAME_LEN {
+        return Err(format!("Room name must be between 1 and {} characters", MAX_NAME_LEN));
+    }
+    validate_rounds(rounds, round_time)
+}
+
+/// Also used for practice sessions, which have no room
+fn validate_rounds(rounds: u32, round_time: u32) -> Result<(), String> {
+    if rounds == 0 || rounds > MAX_ROUNDS {
+        return Err(format!("Number of rounds must be between 1 and {}", MAX_ROUNDS));
+    }
+    if !(MIN_ROUND_TIME_SECS..=MAX_ROUND_TIME_SECS).contains(&round_time) {
+        return Err(format!(
+            "Round time must be between {} and {} seconds",
+            MIN_ROUND_TIME_SECS, MAX_ROUND_TIME_SECS
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test_validate_room_settings() {
+    assert!(validate_room_settings("My room", 9, 75).is_ok());
+    assert!(validate_room_settings("   ", 9, 75).is_err());
+    assert!(validate_room_settings(&"x".repeat(MAX_NAME_LEN + 1), 9, 75).is_err());
+    assert!(validate_room_settings("My room", 0, 75).is_err());
+    assert!(validate_room_settings("My room", 9, 0).is_err());
+    assert!(validate_room_settings("My room", 9, 100000).is_err());
+}
+
+fn validate_create_room_form(form: &CreateRoomForm) -> Result<(), String> {
+    validate_room_settings(&form.room_name, form.rounds, form.round_time)?;
+    let name_len = form.username.trim().chars().count();
+    if name_len == 0 || name_len > MAX_NAME_LEN {
+        return Err(format!("Username must be between 1 and {} characters", MAX_NAME_LEN));
+    }
+    if form.skip_vote_percent >= 100 {
+        return Err("Skip vote percentage must be below 100".into());
+    }
+    let limits = [
+        ("Target score", form.target_score, MAX_TARGET_SCORE),
+        ("Streak bonus", form.streak_bonus, MAX_STREAK_BONUS),
+        ("Maximum streak bonus", form.max_streak_bonus, MAX_STREAK_BONUS * MAX_ROUNDS),
+        ("Guesses per round", form.max_guesses_per_round, MAX_GUESSES_PER_ROUND),
+        ("Guess cooldown", form.guess_cooldown_ms, MAX_GUESS_COOLDOWN_MS),
+        ("Wrong guess penalty", form.wrong_guess_penalty, MAX_WRONG_GUESS_PENALTY),
+        ("Maximum players", form.max_players, MAX_PLAYERS),
+    ];
+    for (setting, value, max) in limits {
+        if value > max {
+            return Err(format!("{} must be at most {}", setting, max));
+        }
+    }
+    Ok(())
+}
+
+/// Error messages must not contain user input, they're put into the page unescaped
+fn create_room_page(state: &State, error: &str) -> Result<String, Error> {
+    Ok(std::fs::read_to_string("frontend/create-room.html")?
+        .replace("GAMEMODEOPTIONS", &game_mode_options_html(GameMode::default()))
+        .replace("THEMEOPTIONS", &theme_options_html(&state.themes, "Custom playlist"))
+        .replace("CREATEERROR", error))
+}
+
+pub async fn get_create_room(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    Ok(axum::response::Html(create_room_page(&state, "")?))
 }
 
 pub async fn post_create_room(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
-    axum::extract::Form(form): axum::extract::Form<CreateRoomForm>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
+    axum::extract::ConnectInfo(address): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    form: Result<axum::extract::Form<CreateRoomForm>, axum::extract::rejection::FormRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Form(form) = form?;
     log::info!("Room created: {:?}", form);
 
     let player_id = gen_id();
+    let token = SessionToken::generate();
 
+    let form_error = |error: &str| match create_room_page(&state, error) {
+        Ok(html) => Error::Html { status: axum::http::StatusCode::BAD_REQUEST, html },
+        Err(e) => e,
+    };
+    validate_create_room_form(&form).map_err(|e| form_error(&e))?;
+    let playlist =
+        chosen_playlist_url(&state.themes, &form.theme, &form.playlist).map_err(form_error)?;
+    if playlist.is_empty() {
+        return Err(form_error("Please select a theme or enter a playlist URL"));
+    }
     let song_provider =
-        SongProvider::from_any_url(state.spotify_client.clone(), &form.playlist).await.unwrap();
+        SongProvider::from_any_url(state.spotify_client.clone(), playlist, form.explicit.is_some())
+            .await
+            .map_err(|e| {
+                log::info!("Couldn't load playlist {:?} for new room: {}", playlist, e);
+                form_error("Couldn't load that playlist")
+            })?;
+    if form.game_mode == GameMode::ReleaseYear && !song_provider.has_release_years() {
+        return Err(form_error("Release year mode only works with Spotify playlists"));
+    }
+    song_provider.prefetch();
 
     let new_room = Room {
         name: form.room_name,
         password: if form.password.is_empty() { None } else { Some(form.password) },
+        unlisted: form.unlisted.is_some(),
         num_rounds: form.rounds,
+        target_score: form.target_score,
         round_time_secs: form.round_time,
+        streak_bonus: form.streak_bonus,
+        max_streak_bonus: form.max_streak_bonus,
+        max_guesses_per_round: form.max_guesses_per_round,
+        guess_cooldown_ms: form.guess_cooldown_ms,
+        wrong_guess_penalty: form.wrong_guess_penalty,
+        skip_vote_percent: form.skip_vote_percent,
+        max_players: form.max_players,
+        spectator_chat: form.spectator_chat.is_some(),
+        game_mode: form.game_mode,
+        audio_modifiers: form.audio_modifier,
+        team_mode: form.team_mode,
+        allow_explicit: form.explicit.is_some(),
         created_at: std::time::Instant::now(),
         song_provider: std::sync::Arc::new(song_provider),
+        owner: Some(player_id),
+        banned_tokens: std::collections::HashSet::new(),
+        banned_ips: std::collections::HashSet::new(),
         players: vec![Player {
             ws: parking_lot::Mutex::new(None),
             name: form.username,
             id: player_id,
+            token: token.clone(),
+            ip: address.ip(),
             loaded: false,
             guessed: None,
+            guessed_by_teammate: false,
+            streak_bonus: 0,
             streak: 0,
             points: 0,
-            emoji: emoji_from_cookies(&cookies).to_string(),
+            guesses_this_round: 0,
+            last_guess_time: None,
+            penalty: 0,
+            chosen: None,
+            snippet: 0,
+            year_guess: None,
+            eliminated: false,
+            voted_skip: false,
+            spectator: false,
+            queued: false,
+            co_host: false,
+            team: None,
+            emoji: emoji_from_cookies(cookies.as_deref()).to_string(),
         }],
+        teams: Vec::new(),
         state: RoomState::Lobby,
         current_round: 0,
+        rounds_without_elimination: 0,
         round_task: None,
         current_song: None,
+        current_modifier: None,
+        choices: Vec::new(),
+        correct_choice: 0,
+        snippets: std::collections::HashMap::new(),
         round_start_time: None,
         empty_last_time_we_checked: false,
     };
 
     let mut rooms = state.rooms.lock();
-    let new_room_id = rooms.keys().max().map_or(0, |largest_id| largest_id + 1);
+    let new_room_id = gen_room_code(&rooms);
     rooms.insert(new_room_id, std::sync::Arc::new(parking_lot::Mutex::new(new_room)));
 
     Ok((
-        axum::response::AppendHeaders([(
-            axum::http::header::SET_COOKIE,
-            format!("user={}; Path=/", player_id.0),
-        )]),
+        axum::response::AppendHeaders([(axum::http::header::SET_COOKIE, session_cookie(&token))]),
         axum::response::Redirect::to(&format!("/room/{}", new_room_id)),
     ))
 }
@@ -212,101 +520,609 @@ pub async fn post_create_room(
 #[derive(serde::Deserialize)]
 pub struct RoomSettings {
     room_name: String,
+    #[serde(default)]
+    game_mode: GameMode,
     rounds: u32,
     round_time: u32,
+    /// Empty to keep the current playlist
+    #[serde(default)]
+    theme: String,
+    /// Empty to keep the current playlist, ignored if a theme is selected
+    #[serde(default)]
+    playlist: String,
+    /// Checkbox, only sent when checked
+    #[serde(default)]
+    explicit: Option<String>,
+}
+
+/// `<option>` elements for the game mode `<select>`, with the given mode preselected
+fn game_mode_options_html(selected: GameMode) -> String {
+    GameMode::ALL
+        .iter()
+        .map(|&mode| {
+            // The Debug output is the variant name, which is what the form deserializes
+            format!(
+                "<option {}value=\"{:?}\">{}</option>",
+                if mode == selected { "selected " } else { "" },
+                mode,
+                mode.name()
+            )
+        })
+        .collect()
 }
 
 fn get_or_post_room(
     state: std::sync::Arc<State>,
-    cookies: axum::headers::Cookie,
+    cookies: Option<&axum::headers::Cookie>,
     room_id: u32,
-    apply_settings: Option<RoomSettings>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
-    let player_id = cookies
-        .get("user")
-        .ok_or_else(|| axum::response::Redirect::to(&format!("/join/{}", room_id)))?;
-    let player_id = PlayerId(player_id.parse().unwrap());
-    let room = state
-        .rooms
-        .lock()
-        .get(&room_id)
-        .ok_or_else(|| axum::response::Redirect::to("/server-browser"))?
-        .clone();
+    apply_settings: Option<(RoomSettings, Option<SongProvider>)>,
+    settings_error: &str,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let token =
+        session_token(cookies).ok_or_else(|| Error::redirect(format!("/join/{}", room_id)))?;
+    let room =
+        state.rooms.lock().get(&room_id).ok_or_else(|| Error::redirect("/server-browser"))?.clone();
 
     let mut room = room.lock();
-    if !room.players.iter().any(|p| p.id == player_id) {
-        return Err(axum::response::Redirect::to(&format!("/join/{}", room_id)).into());
-    }
+    let player_id = room
+        .player_by_token(&token)
+        .ok_or_else(|| Error::redirect(format!("/join/{}", room_id)))?
+        .id;
 
-    if let Some(RoomSettings { room_name, rounds, round_time }) = apply_settings {
-        log::info!("User {} changed settings for room {}", player_id.0, room_id);
-        room.name = room_name;
-        room.num_rounds = rounds;
-        room.round_time_secs = round_time;
+    let mut settings_error = settings_error.to_string();
+    if let Some((
+        RoomSettings { room_name, game_mode, rounds, round_time, explicit, .. },
+        song_provider,
+    )) = apply_settings
+    {
+        if !room.is_host(player_id) {
+            return Err(Error::page(
+                axum::http::StatusCode::FORBIDDEN,
+                "Only the host can change the room settings",
+            ));
+        }
+        if room.state != RoomState::Lobby {
+            return Err(Error::page(
+                axum::http::StatusCode::CONFLICT,
+                "Settings can't be changed while a game is running",
+            ));
+        }
+        if !settings_error.is_empty() {
+            // Apply all or nothing, so that the host doesn't see an error with half the form saved
+        } else if let Err(e) = validate_room_settings(&room_name, rounds, round_time) {
+            settings_error = e;
+        } else if game_mode == GameMode::ReleaseYear
+            && !song_provider.as_ref().unwrap_or(&room.song_provider).has_release_years()
+        {
+            settings_error = "Release year mode only works with Spotify playlists".into();
+        } else {
+            log::info!("User {} changed settings for room {}", player_id.0, room_id);
+            room.name = room_name;
+            room.game_mode = game_mode;
+            room.num_rounds = rounds;
+            room.round_time_secs = round_time;
+            room.allow_explicit = explicit.is_some();
+            room.song_provider.set_allow_explicit(room.allow_explicit);
+            if let Some(song_provider) = song_provider {
+                song_provider.prefetch();
+                room.song_provider = std::sync::Arc::new(song_provider);
+                room.send_all(&SendEvent::Notify {
+                    message: format!("The playlist is now {}", room.song_provider.playlist_name()),
+                });
+            }
+        }
     }
 
     let html = match room.state {
-        RoomState::Lobby => std::fs::read_to_string("frontend/roomLOBBY.html")
-            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        RoomState::Lobby => std::fs::read_to_string("frontend/roomLOBBY.html")?
             .replace("ROOMID", &room_id.to_string())
-            .replace("ROOMNAME", &room.name)
+            .replace("PLAYERID", &player_id.0.to_string())
+            .replace("SETTINGSERROR", &settings_error)
+            .replace("EXPLICITCHECKED", if room.allow_explicit { "checked" } else { "" })
+            .replace("GAMEMODEOPTIONS", &game_mode_options_html(room.game_mode))
+            .replace("CURRENTROUNDS", &room.num_rounds.to_string())
+            .replace("CURRENTROUNDTIME", &room.round_time_secs.to_string())
+            .replace("THEMEOPTIONS", &theme_options_html(&state.themes, "Keep current playlist"))
+            // Last, so that placeholders in the user-chosen name aren't replaced
+            .replace("ROOMNAME", &escape_html(&room.name)),
+        RoomState::WaitingForLoaded
+        | RoomState::WaitingForReconnect
+        | RoomState::RoundStarted
+        | RoomState::RoundEnded => std::fs::read_to_string("frontend/roomPLAY.html")?
+            .replace("ROOMID", &room_id.to_string())
+            .replace("CURRENTGAMEMODE", &format!("{:?}", room.game_mode))
             .replace("PLAYERID", &player_id.0.to_string()),
-        RoomState::WaitingForLoaded | RoomState::WaitingForReconnect | RoomState::RoundStarted => {
-            std::fs::read_to_string("frontend/roomPLAY.html")
-                .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
-                .replace("ROOMID", &room_id.to_string())
-                .replace("PLAYERID", &player_id.0.to_string())
-        }
     };
     Ok(axum::response::Html(html))
 }
 
 pub async fn get_room(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
-    axum::extract::Path(room_id): axum::extract::Path<u32>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
-    get_or_post_room(state, cookies, room_id, None)
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    room_id: Result<axum::extract::Path<u32>, axum::extract::rejection::PathRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(room_id) = room_id?;
+    get_or_post_room(state, cookies.as_deref(), room_id, None, "")
 }
 
 pub async fn post_room(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
-    axum::extract::Path(room_id): axum::extract::Path<u32>,
-    axum::extract::Form(room_settings): axum::extract::Form<RoomSettings>,
-) -> Result<impl axum::response::IntoResponse, axum::response::ErrorResponse> {
-    get_or_post_room(state, cookies, room_id, Some(room_settings))
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    room_id: Result<axum::extract::Path<u32>, axum::extract::rejection::PathRejection>,
+    room_settings: Result<
+        axum::extract::Form<RoomSettings>,
+        axum::extract::rejection::FormRejection,
+    >,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(room_id) = room_id?;
+    let axum::extract::Form(room_settings) = room_settings?;
+    let mut settings_error = "";
+    let mut song_provider = None;
+    let playlist =
+        chosen_playlist_url(&state.themes, &room_settings.theme, &room_settings.playlist)
+            .unwrap_or_else(|e| {
+                settings_error = e;
+                ""
+            });
+    // Only hosts may make us download a playlist, and only if the settings will be applied.
+    // get_or_post_room does the actual checks and error reporting
+    let is_host_in_lobby = state.rooms.lock().get(&room_id).is_some_and(|room| {
+        let room = room.lock();
+        room.state == RoomState::Lobby
+            && session_token(cookies.as_deref())
+                .and_then(|token| room.player_by_token(&token).map(|p| p.id))
+                .is_some_and(|player_id| room.is_host(player_id))
+    });
+    let valid = validate_room_settings(
+        &room_settings.room_name,
+        room_settings.rounds,
+        room_settings.round_time,
+    )
+    .is_ok();
+    if is_host_in_lobby && valid && !playlist.is_empty() {
+        let allow_explicit = room_settings.explicit.is_some();
+        match SongProvider::from_any_url(state.spotify_client.clone(), playlist, allow_explicit)
+            .await
+        {
+            Ok(new_song_provider) => song_provider = Some(new_song_provider),
+            Err(e) => {
+                log::info!("Couldn't load playlist {:?} for room {}: {}", playlist, room_id, e);
+                settings_error = "Couldn't load that playlist";
+            }
+        }
+    }
+    get_or_post_room(
+        state,
+        cookies.as_deref(),
+        room_id,
+        Some((room_settings, song_provider)),
+        settings_error,
+    )
+}
+
+/// Looks up the room and session token for the websocket and song routes, which are fetched by
+/// scripts and therefore answer with JSON errors
+fn room_and_token(
+    state: &State,
+    room_id: u32,
+    cookies: Option<&axum::headers::Cookie>,
+) -> Result<(std::sync::Arc<parking_lot::Mutex<Room>>, SessionToken), Error> {
+    let token = session_token(cookies).ok_or_else(|| {
+        Error::json(axum::http::StatusCode::UNAUTHORIZED, "Please rejoin the room")
+    })?;
+    let room = state
+        .rooms
+        .lock()
+        .get(&room_id)
+        .ok_or_else(|| Error::json(axum::http::StatusCode::NOT_FOUND, "This room doesn't exist"))?
+        .clone();
+    Ok((room, token))
+}
+
+fn not_in_room() -> Error {
+    Error::json(axum::http::StatusCode::FORBIDDEN, "You're not in this room, please rejoin it")
 }
 
 pub async fn get_room_ws(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::Path(room_id): axum::extract::Path<u32>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
+    room_id: Result<axum::extract::Path<u32>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
     ws: axum::extract::WebSocketUpgrade,
-) -> impl axum::response::IntoResponse {
-    let player_id = PlayerId(cookies.get("user").unwrap().parse().unwrap());
-    let room = state.rooms.lock().get(&room_id).unwrap().clone();
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(room_id) = room_id.map_err(|e| Error::from(e).into_json())?;
+    let (room, token) = room_and_token(&state, room_id, cookies.as_deref())?;
+    // Players only exist in the room after passing the password check in post_join
+    let player_id = room.lock().player_by_token(&token).ok_or_else(not_in_room)?.id;
     log::info!("User {} connected via websocket to room {}", player_id.0, room_id);
 
-    ws.on_upgrade(move |ws| async move {
+    Ok(ws.on_upgrade(move |ws| async move {
         websocket_connect(room, player_id, std::sync::Arc::new(WebSocket::new(ws))).await;
-    })
+    }))
 }
 
 pub async fn get_song(
     axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
-    axum::extract::Path((_player_id, room_id, _random)): axum::extract::Path<(u64, u32, u64)>,
-    axum::extract::TypedHeader(cookies): axum::extract::TypedHeader<axum::headers::Cookie>,
+    path: Result<axum::extract::Path<(u64, u32, u64)>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path((_player_id, room_id, _random)) =
+        path.map_err(|e| Error::from(e).into_json())?;
+    let (room, token) = room_and_token(&state, room_id, cookies.as_deref())?;
+
+    let (audio, snippet_secs, snippet) = {
+        let mut room = room.lock();
+
+        let player = room.player_by_token(&token).ok_or_else(not_in_room)?;
+        let Some(song) = &room.current_song else {
+            return Err(Error::json(
+                axum::http::StatusCode::CONFLICT,
+                "No song is playing right now",
+            ));
+        };
+        if room.game_mode != GameMode::Heardle {
+            return Ok(song.audio.clone());
+        }
+
+        let snippet_secs = HEARDLE_SNIPPET_SECS[player.snippet];
+        let snippet = room.snippets.entry(snippet_secs).or_default().clone();
+        if let Some(snippet) = snippet.get() {
+            return Ok(snippet.clone());
+        }
+        let audio = room.current_song.as_ref().expect("checked above").audio.clone();
+        (audio, snippet_secs, snippet)
+    };
+
+    // Not holding the room lock while ffmpeg runs. Players asking for the same snippet meanwhile
+    // wait for this clip instead of starting their own
+    let snippet = snippet.get_or_try_init(|| clip(&audio, snippet_secs)).await.map_err(|e| {
+        log::error!("Couldn't clip song for room {}: {}", room_id, e);
+        Error::json(axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Couldn't load the song")
+    })?;
+    Ok(snippet.clone())
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PostPracticeForm {
+    playlist: String,
+    rounds: u32,
+    round_time: u32,
+}
+
+#[derive(serde::Serialize)]
+pub struct PracticeResponse {
+    session: u64,
+    #[serde(flatten)]
+    state: PracticeState,
+    correct: Option<bool>, // Only in response to a guess
+    finished: bool,
+    personal_best: Option<u32>,
+}
+
+fn get_practice_session(
+    state: &State,
+    session_id: u64,
+    cookies: Option<&axum::headers::Cookie>,
+) -> Result<std::sync::Arc<parking_lot::Mutex<PracticeSession>>, Error> {
+    let session = state
+        .practice_sessions
+        .lock()
+        .get(&session_id)
+        .ok_or_else(|| {
+            Error::json(axum::http::StatusCode::NOT_FOUND, "This practice session has ended")
+        })?
+        .clone();
+    {
+        let mut session = session.lock();
+        if cookies.and_then(|cookies| cookies.get("practice")) != Some(session.user.as_str()) {
+            return Err(Error::json(
+                axum::http::StatusCode::FORBIDDEN,
+                "This is someone else's practice session",
+            ));
+        }
+        session.last_activity = std::time::Instant::now();
+    }
+    Ok(session)
+}
+
+fn no_playable_songs() -> Error {
+    Error::json(axum::http::StatusCode::BAD_REQUEST, "That playlist has no playable songs")
+}
+
+fn practice_response(
+    state: &State,
+    session_id: u64,
+    session: &mut PracticeSession,
+    correct: Option<bool>,
+    finished: bool,
+) -> axum::Json<PracticeResponse> {
+    let personal_best = state
+        .personal_bests
+        .lock()
+        .get(&(session.user.clone(), session.playlist_url.clone()))
+        .copied();
+    axum::Json(PracticeResponse {
+        session: session_id,
+        state: session.state(),
+        correct,
+        finished,
+        personal_best,
+    })
+}
+
+pub async fn post_practice(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    form: Result<axum::extract::Form<PostPracticeForm>, axum::extract::rejection::FormRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Form(form) = form.map_err(|e| Error::from(e).into_json())?;
+    log::info!("Practice session started: {:?}", form);
+    validate_rounds(form.rounds, form.round_time)
+        .map_err(|e| Error::json(axum::http::StatusCode::BAD_REQUEST, e))?;
+
+    let user = user_from_cookies(cookies.as_deref());
+    let song_provider =
+        SongProvider::from_any_url(state.spotify_client.clone(), &form.playlist, true)
+            .await
+            .map_err(|e| {
+                log::info!("Couldn't load playlist {:?} for practice: {}", form.playlist, e);
+                Error::json(axum::http::StatusCode::BAD_REQUEST, "Couldn't load that playlist")
+            })?;
+    let songs = PracticeSongs::Playlist(std::sync::Arc::new(song_provider));
+    let first_song = songs.song_for_round(0).await.ok_or_else(no_playable_songs)?;
+
+    let session = PracticeSession::new(
+        user,
+        form.playlist,
+        songs,
+        None,
+        first_song,
+        form.rounds,
+        form.round_time,
+    );
+    Ok(start_practice_session(&state, session))
+}
+
+/// Practice sessions, personal bests and daily attempts are keyed on their own cookie, because the
+/// `user` cookie is replaced on every room join
+fn user_from_cookies(cookies: Option<&axum::headers::Cookie>) -> String {
+    match cookies.and_then(|cookies| cookies.get("practice")) {
+        Some(user) => user.to_string(),
+        None => SessionToken::generate().0,
+    }
+}
+
+fn practice_cookie(user: &str) -> String {
+    format!("practice={}; Path=/; Max-Age=31536000; HttpOnly; SameSite=Lax", user)
+}
+
+/// Unguessable, so that nobody can play someone else's session
+fn gen_practice_session_id() -> u64 {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator failed");
+    u64::from_le_bytes(bytes)
+}
+
+fn start_practice_session(
+    state: &State,
+    mut session: PracticeSession,
 ) -> impl axum::response::IntoResponse {
-    let player_id = PlayerId(cookies.get("user").unwrap().parse().unwrap());
-    let room = state.rooms.lock().get(&room_id).unwrap().clone();
+    let user = session.user.clone();
+    let session_id = gen_practice_session_id();
+    let response = practice_response(state, session_id, &mut session, None, false);
+    state
+        .practice_sessions
+        .lock()
+        .insert(session_id, std::sync::Arc::new(parking_lot::Mutex::new(session)));
 
-    let room = room.lock();
+    (
+        axum::response::AppendHeaders([(axum::http::header::SET_COOKIE, practice_cookie(&user))]),
+        response,
+    )
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PostDailyForm {
+    username: String,
+}
 
-    if !room.players.iter().any(|p| p.id == player_id) {
-        return Err(axum::http::StatusCode::UNAUTHORIZED);
+pub async fn post_daily(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    axum::extract::ConnectInfo(address): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    form: Result<axum::extract::Form<PostDailyForm>, axum::extract::rejection::FormRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Form(form) = form.map_err(|e| Error::from(e).into_json())?;
+    log::info!("Daily challenge started: {:?}", form);
+
+    let Some(daily_playlist) = &state.daily_playlist else {
+        return Err(Error::json(
+            axum::http::StatusCode::NOT_FOUND,
+            "No daily challenge on this server",
+        ));
+    };
+    let name_len = form.username.trim().chars().count();
+    if name_len == 0 || name_len > MAX_NAME_LEN {
+        return Err(Error::json(
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Username must be between 1 and {} characters", MAX_NAME_LEN),
+        ));
+    }
+    let user = user_from_cookies(cookies.as_deref());
+    let day = today();
+
+    let is_outdated =
+        |daily: &Option<DailyChallenge>| !matches!(daily, Some(daily) if daily.day == day);
+    if is_outdated(&*state.daily.lock().await) {
+        let _downloading = state.daily_download.lock().await;
+        // Another request may have downloaded it while we waited
+        if is_outdated(&*state.daily.lock().await) {
+            let new_daily =
+                DailyChallenge::download(state.spotify_client.clone(), daily_playlist, day)
+                    .await
+                    .map_err(|e| {
+                    log::error!("Couldn't download daily challenge: {}", e);
+                    Error::json(
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        "Couldn't load today's daily challenge",
+                    )
+                })?;
+            *state.daily.lock().await = Some(new_daily);
+        }
     }
-    Ok(room.current_song.as_ref().unwrap().audio.clone())
+
+    let songs = {
+        let mut daily = state.daily.lock().await;
+        let Some(daily) = daily.as_mut().filter(|daily| daily.day == day) else {
+            return Err(Error::json(
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                "A new daily challenge just started, please try again",
+            ));
+        };
+
+        if daily.songs.is_empty() {
+            return Err(Error::json(
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Today's daily challenge has no songs",
+            ));
+        }
+        if !daily.start(&user, address.ip()) {
+            return Err(Error::json(
+                axum::http::StatusCode::FORBIDDEN,
+                "You already played today's daily challenge",
+            ));
+        }
+        daily.songs.clone()
+    };
+
+    let emoji = emoji_from_cookies(cookies.as_deref());
+    let session = PracticeSession::new(
+        user,
+        daily_playlist.clone(),
+        PracticeSongs::Fixed(songs.clone()),
+        Some(DailyParticipant {
+            day,
+            username: form.username.trim().to_string(),
+            emoji: emoji.to_string(),
+        }),
+        songs[0].clone(),
+        songs.len() as u32,
+        DAILY_ROUND_TIME_SECS,
+    );
+    Ok(start_practice_session(&state, session))
+}
+
+pub async fn get_leaderboard(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let mut results = match &*state.daily.lock().await {
+        Some(daily) if daily.day == today() => daily.results.clone(),
+        _ => Vec::new(),
+    };
+    results.sort_by_key(|result| std::cmp::Reverse(result.score));
+
+    // Escape "</" so that usernames can't close the <script> tag this is inserted into
+    let results = serde_json::to_string(&results)
+        .map_err(|e| {
+            log::error!("Couldn't serialize leaderboard: {}", e);
+            Error::page(
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Couldn't load the leaderboard",
+            )
+        })?
+        .replace("</", "<\\/");
+    let html =
+        std::fs::read_to_string("frontend/leaderboard.html")?.replace("LEADERBOARD_DATA", &results);
+    Ok(axum::response::Html(html))
+}
+
+pub async fn get_practice(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    session_id: Result<axum::extract::Path<u64>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(session_id) = session_id.map_err(|e| Error::from(e).into_json())?;
+    let session = get_practice_session(&state, session_id, cookies.as_deref())?;
+    let mut session = session.lock();
+    Ok(practice_response(&state, session_id, &mut session, None, false))
+}
+
+pub async fn get_practice_song(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    session_id: Result<axum::extract::Path<u64>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(session_id) = session_id.map_err(|e| Error::from(e).into_json())?;
+    let session = get_practice_session(&state, session_id, cookies.as_deref())?;
+    let audio = session.lock().current_song.audio.clone();
+    Ok(audio)
+}
+
+#[derive(serde::Deserialize)]
+pub struct PracticeGuessForm {
+    guess: String,
+}
+
+pub async fn post_practice_guess(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    session_id: Result<axum::extract::Path<u64>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+    form: Result<axum::extract::Form<PracticeGuessForm>, axum::extract::rejection::FormRejection>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(session_id) = session_id.map_err(|e| Error::from(e).into_json())?;
+    let axum::extract::Form(form) = form.map_err(|e| Error::from(e).into_json())?;
+    let session = get_practice_session(&state, session_id, cookies.as_deref())?;
+    let mut session = session.lock();
+    let correct = session.guess(&form.guess).is_some();
+    Ok(practice_response(&state, session_id, &mut session, Some(correct), false))
+}
+
+pub async fn post_practice_next(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<State>>,
+    session_id: Result<axum::extract::Path<u64>, axum::extract::rejection::PathRejection>,
+    cookies: Option<axum::extract::TypedHeader<axum::headers::Cookie>>,
+) -> Result<impl axum::response::IntoResponse, Error> {
+    let axum::extract::Path(session_id) = session_id.map_err(|e| Error::from(e).into_json())?;
+    let session = get_practice_session(&state, session_id, cookies.as_deref())?;
+
+    let (songs, next_round) = {
+        let mut session = session.lock();
+
+        if session.is_last_round() {
+            state.practice_sessions.lock().remove(&session_id);
+
+            if let Some(daily) = &session.daily {
+                let result = DailyResult {
+                    username: daily.username.clone(),
+                    emoji: daily.emoji.clone(),
+                    score: session.points,
+                };
+                let day = daily.day;
+                let state = state.clone();
+                // Can't await the daily lock while holding the session lock
+                tokio::spawn(async move {
+                    if let Some(daily) = &mut *state.daily.lock().await {
+                        if daily.day == day {
+                            daily.results.push(result);
+                        }
+                    }
+                });
+            } else {
+                let key = (session.user.clone(), session.playlist_url.clone());
+                let mut personal_bests = state.personal_bests.lock();
+                let personal_best = personal_bests.entry(key).or_insert(0);
+                *personal_best = u32::max(*personal_best, session.points);
+            }
+
+            return Ok(practice_response(&state, session_id, &mut session, None, true));
+        }
+
+        (session.songs.clone(), session.current_round + 1)
+    };
+    let song = songs.song_for_round(next_round).await.ok_or_else(no_playable_songs)?;
+
+    let mut session = session.lock();
+    session.start_next_round(song);
+    Ok(practice_response(&state, session_id, &mut session, None, false))
 }
 
 pub async fn fallback(uri: axum::http::Uri) -> impl axum::response::IntoResponse {
@@ -332,9 +1148,27 @@ pub async fn run_axum(spotify_client: rspotify::ClientCredsSpotify) {
             std::sync::Arc::new(parking_lot::Mutex::new(Room {
                 name: "starter room lol".to_string(),
                 players: Vec::new(),
+                owner: None,
+                banned_tokens: std::collections::HashSet::new(),
+                banned_ips: std::collections::HashSet::new(),
+                teams: Vec::new(),
                 password: None,
+                unlisted: false,
                 num_rounds: 9,
+                target_score: 0,
                 round_time_secs: 75,
+                streak_bonus: 0,
+                max_streak_bonus: 0,
+                max_guesses_per_round: 0,
+                guess_cooldown_ms: 0,
+                wrong_guess_penalty: 0,
+                skip_vote_percent: default_skip_vote_percent(),
+                max_players: 0,
+                spectator_chat: true,
+                game_mode: GameMode::Themes,
+                audio_modifiers: Vec::new(),
+                team_mode: TeamMode::Off,
+                allow_explicit: true,
                 created_at: std::time::Instant::now(),
                 state: RoomState::Lobby,
                 round_task: None,
@@ -342,17 +1176,30 @@ pub async fn run_axum(spotify_client: rspotify::ClientCredsSpotify) {
                     SongProvider::from_spotify_playlist(
                         spotify_client.clone(),
                         "5wWUVh8qv6YygjbNZCckFl",
+                        true,
                     )
                     .await
                     .unwrap(),
                 ),
                 current_song: None,
+                current_modifier: None,
+                choices: Vec::new(),
+                correct_choice: 0,
+                snippets: std::collections::HashMap::new(),
                 round_start_time: None,
                 current_round: 0,
+                rounds_without_elimination: 0,
                 empty_last_time_we_checked: false,
             })),
         )])),
         spotify_client,
+        practice_sessions: parking_lot::Mutex::new(std::collections::HashMap::new()),
+        personal_bests: parking_lot::Mutex::new(std::collections::HashMap::new()),
+        daily_playlist: std::env::var("DAILY_PLAYLIST").ok(),
+        daily: tokio::sync::Mutex::new(None),
+        daily_download: tokio::sync::Mutex::new(()),
+        failed_joins: RateLimiter::new(MAX_FAILED_JOINS, FAILED_JOINS_WINDOW),
+        themes: load_themes(),
     });
 
     let state2 = state.clone();
@@ -371,6 +1218,24 @@ pub async fn run_axum(spotify_client: rspotify::ClientCredsSpotify) {
                 room.empty_last_time_we_checked = room.players.is_empty();
                 true
             });
+            // Handlers lock the session map while holding a session, so we must not lock the
+            // sessions while holding the map
+            let sessions = state2
+                .practice_sessions
+                .lock()
+                .iter()
+                .map(|(&id, session)| (id, session.clone()))
+                .collect::<Vec<_>>();
+            let expired = sessions
+                .into_iter()
+                .filter(|(_, session)| {
+                    session.lock().last_activity.elapsed()
+                        >= std::time::Duration::from_secs(30 * 60)
+                })
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            state2.practice_sessions.lock().retain(|id, _| !expired.contains(id));
+            state2.failed_joins.purge();
 
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
         }
@@ -378,15 +1243,27 @@ pub async fn run_axum(spotify_client: rspotify::ClientCredsSpotify) {
 
     let app = axum::Router::new()
         .route("/server-browser/ws", axum::routing::get(get_server_browser_ws))
-        .route("/create-room.html", axum::routing::get(fallback).post(post_create_room))
+        .route("/create-room", axum::routing::get(get_create_room))
+        .route("/create-room.html", axum::routing::get(get_create_room).post(post_create_room))
         .route("/join/:room_id", axum::routing::get(get_join).post(post_join))
         .route("/room/:room_id", axum::routing::get(get_room).post(post_room))
         .route("/room/:room_id/ws", axum::routing::get(get_room_ws))
         .route("/song/:player_id/:room_id/:random", axum::routing::get(get_song))
+        .route("/practice", axum::routing::post(post_practice))
+        .route("/daily", axum::routing::post(post_daily))
+        .route("/leaderboard", axum::routing::get(get_leaderboard))
+        .route("/leaderboard.html", axum::routing::get(get_leaderboard))
+        .route("/practice/:session_id", axum::routing::get(get_practice))
+        .route("/practice/:session_id/song", axum::routing::get(get_practice_song))
+        .route("/practice/:session_id/guess", axum::routing::post(post_practice_guess))
+        .route("/practice/:session_id/next", axum::routing::post(post_practice_next))
         .fallback(fallback)
         .with_state(state);
 
     let address = std::net::SocketAddr::from(([0, 0, 0, 0], 8787));
     log::info!("Running HTTP server at http://{:?}", address);
-    axum::Server::bind(&address).serve(app.into_make_service()).await.unwrap();
+    axum::Server::bind(&address)
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await
+        .unwrap();
 }
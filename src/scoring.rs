@@ -0,0 +1,49 @@
+pub fn title_matches(title: &str, input: &str) -> bool {
+    fn strip_punctuation_and_make_lowercase(s: &str) -> String {
+        s.chars().filter(|c| c.is_alphabetic()).flat_map(|c| c.to_lowercase()).collect()
+    }
+    let title = strip_punctuation_and_make_lowercase(title);
+    let input = strip_punctuation_and_make_lowercase(input);
+    levenshtein::levenshtein(&title, &input) <= title.len() / 10
+}
+
+#[cfg(test)]
+#[test]
+fn test_title_matches() {
+    assert!(title_matches("Mr. Vain", "mr vain"));
+    assert!(title_matches("Everybody (Backstreet's Back)", "everybody backstreets back"));
+    assert!(title_matches("You Spin Me Round", "you spin me roun"));
+    assert!(!title_matches("Jump", "Jum"));
+    assert!(!title_matches("Wonderwall", "Wonder"));
+}
+
+/// `guess_time` is in seconds since the round started
+pub fn points_for_guess(
+    guess_time: f32,
+    round_time_secs: u32,
+    how_many_others_have_already_guessed: usize,
+) -> u32 {
+    let hints_left = ((round_time_secs as f32 - guess_time) / 10.0) as u32;
+
+    // This is the original GuessTheSong algorithm as posted by "Frank (11studios)"
+    // in the GuessTheSong.io Discord server
+    // https://discord.com/channels/741670496822886470/741670497304969232/1092483679261053078
+    let mut points = 100;
+    match guess_time {
+        x if x < 10.0 => points += 125,
+        x if x < 20.0 => points += 100,
+        x if x < 25.0 => points += 75,
+        x if x < 45.0 => points += 62,
+        x if x < 70.0 => points += 50,
+        _ => points += 25,
+    }
+    match how_many_others_have_already_guessed {
+        0 => points += 200,
+        1 => points += 150,
+        2 => points += 100,
+        _ => {}
+    }
+    points += u32::min(hints_left * 25, 100);
+
+    points
+}
@@ -5,6 +5,7 @@ use crate::ytdlp_download::*;
 pub struct Song {
     pub title: String,
     pub audio: Vec<u8>,
+    pub release_year: Option<u32>, // Only known for Spotify songs
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -57,66 +58,243 @@ fn test_sanitize_spotify_title() {
     );
 }
 
+/// Spotify release dates are "YYYY", "YYYY-MM" or "YYYY-MM-DD" depending on precision. Unknown
+/// dates are sometimes given as "0000"
+fn release_year_from_date(date: &str) -> Option<u32> {
+    date.split('-').next()?.parse().ok().filter(|&year| year != 0)
+}
+
+#[cfg(test)]
+#[test]
+fn test_release_year_from_date() {
+    assert_eq!(release_year_from_date("1985"), Some(1985));
+    assert_eq!(release_year_from_date("1985-06"), Some(1985));
+    assert_eq!(release_year_from_date("1985-06-01"), Some(1985));
+    assert_eq!(release_year_from_date(""), None);
+    assert_eq!(release_year_from_date("0000"), None);
+}
+
+#[allow(clippy::large_enum_variant)] // There's only ever one per room
 enum PlaylistSource {
     Spotify { playlist: SpotifyPlaylist, indices_not_played_yet: parking_lot::Mutex<Vec<usize>> },
     Youtube { tracks: Vec<YtdlpPlaylistEntry> },
 }
 
-async fn download_random_song(playlist: &PlaylistSource) -> Song {
+fn playlist_len(playlist: &PlaylistSource) -> usize {
     match playlist {
-        PlaylistSource::Spotify { playlist, indices_not_played_yet } => {
-            // Select random track
-            let track_index = {
-                let mut indices_not_played_yet = indices_not_played_yet.lock();
-                if indices_not_played_yet.is_empty() {
-                    log::info!("dang either the playlist is tiny or the lobby runs very long");
-                    *indices_not_played_yet = (0..playlist.len()).collect();
-                }
-                let x = fastrand::usize(..indices_not_played_yet.len());
-                indices_not_played_yet.remove(x)
-            };
-            let track = playlist.track(track_index).await.expect("index cant be out of bounds");
+        PlaylistSource::Spotify { playlist, .. } => playlist.len(),
+        PlaylistSource::Youtube { tracks } => tracks.len(),
+    }
+}
+
+async fn download_song(playlist: &PlaylistSource, index: usize) -> Song {
+    match playlist {
+        PlaylistSource::Spotify { playlist, .. } => {
+            let track = playlist.track(index).await.expect("index cant be out of bounds");
 
             // Build youtube search query
-            let (artists, title) = match &track {
-                rspotify::model::PlayableItem::Track(track) => {
-                    (track.artists.iter().map(|x| &*x.name).collect::<Vec<_>>(), &*track.name)
-                }
+            let (artists, title, release_date) = match &track {
+                rspotify::model::PlayableItem::Track(track) => (
+                    track.artists.iter().map(|x| &*x.name).collect::<Vec<_>>(),
+                    &*track.name,
+                    track.album.release_date.as_deref(),
+                ),
                 rspotify::model::PlayableItem::Episode(episode) => {
-                    (vec![&*episode.show.publisher], &*episode.name)
+                    (vec![&*episode.show.publisher], &*episode.name, Some(&*episode.release_date))
                 }
             };
 
             Song {
-                title: sanitize_spotify_title(&title),
-                audio: download_best_effort(&artists, &title).await,
+                title: sanitize_spotify_title(title),
+                audio: download_best_effort(&artists, title).await,
+                release_year: release_date.and_then(release_year_from_date),
+            }
+        }
+        PlaylistSource::Youtube { tracks } => {
+            let song = &tracks[index];
+
+            Song {
+                title: song.title.clone(),
+                audio: download_url(&song.url).await,
+                release_year: None,
+            }
+        }
+    }
+}
+
+/// Lowercase words in YouTube titles that mark a video as explicit, since there's no flag for it
+const EXPLICIT_TITLE_KEYWORDS: &[&str] =
+    &["explicit", "uncensored", "uncut", "dirty version", "dirty mix", "nsfw", "parental advisory"];
+
+/// How often a filtered out song is replaced by another random one before going through the whole
+/// playlist
+const MAX_EXPLICIT_REROLLS: usize = 20;
+
+async fn is_explicit(playlist: &PlaylistSource, index: usize) -> bool {
+    match playlist {
+        PlaylistSource::Spotify { playlist, .. } => {
+            match playlist.track(index).await.expect("index cant be out of bounds") {
+                rspotify::model::PlayableItem::Track(track) => track.explicit,
+                rspotify::model::PlayableItem::Episode(episode) => episode.explicit,
             }
         }
         PlaylistSource::Youtube { tracks } => {
-            let song = tracks[fastrand::usize(0..tracks.len())].clone();
+            let title = tracks[index].title.to_lowercase();
+            EXPLICIT_TITLE_KEYWORDS.iter().any(|keyword| title.contains(keyword))
+        }
+    }
+}
+
+fn random_track_index(playlist: &PlaylistSource) -> usize {
+    match playlist {
+        PlaylistSource::Spotify { playlist, indices_not_played_yet } => {
+            let mut indices_not_played_yet = indices_not_played_yet.lock();
+            if indices_not_played_yet.is_empty() {
+                log::info!("dang either the playlist is tiny or the lobby runs very long");
+                *indices_not_played_yet = (0..playlist.len()).collect();
+            }
+            let x = fastrand::usize(..indices_not_played_yet.len());
+            indices_not_played_yet.remove(x)
+        }
+        PlaylistSource::Youtube { tracks } => fastrand::usize(0..tracks.len()),
+    }
+}
+
+/// Random index of a song that isn't explicit, None if the playlist has none
+async fn random_non_explicit_track_index(playlist: &PlaylistSource) -> Option<usize> {
+    for _ in 0..MAX_EXPLICIT_REROLLS {
+        let track_index = random_track_index(playlist);
+        if !is_explicit(playlist, track_index).await {
+            return Some(track_index);
+        }
+    }
 
-            Song { title: song.title.clone(), audio: download_url(&song.url).await }
+    // Mostly explicit playlist, so look at every song once
+    let mut indices = (0..playlist_len(playlist)).collect::<Vec<_>>();
+    fastrand::shuffle(&mut indices);
+    for track_index in indices {
+        if !is_explicit(playlist, track_index).await {
+            return Some(track_index);
         }
     }
+    None
+}
+
+async fn download_random_song(playlist: &PlaylistSource, allow_explicit: bool) -> Option<Song> {
+    let track_index = if allow_explicit {
+        random_track_index(playlist)
+    } else {
+        let track_index = random_non_explicit_track_index(playlist).await;
+        if track_index.is_none() {
+            log::warn!("playlist has no non-explicit songs");
+        }
+        track_index?
+    };
+    Some(download_song(playlist, track_index).await)
+}
+
+async fn load_playlist_from_any_url(
+    client: std::sync::Arc<rspotify::ClientCredsSpotify>,
+    url: &str,
+) -> Result<PlaylistSource, Box<dyn std::error::Error + Send + Sync>> {
+    use once_cell::sync::Lazy;
+    static SPOTIFY_URL_REGEX: Lazy<regex::Regex> =
+        Lazy::new(|| regex::Regex::new("spotify.com/playlist/([^?/]+)").expect("impossible"));
+    if let Some(captures) = SPOTIFY_URL_REGEX.captures(url) {
+        let playlist_id = captures.get(1).expect("impossible").as_str();
+        return load_spotify_playlist(client, playlist_id).await;
+    }
+    if url.contains("youtube.com") {
+        return load_youtube_playlist(url).await;
+    }
+    Err(format!("invalid URL: {}", url).into())
+}
+
+async fn load_spotify_playlist(
+    client: std::sync::Arc<rspotify::ClientCredsSpotify>,
+    playlist_id: &str,
+) -> Result<PlaylistSource, Box<dyn std::error::Error + Send + Sync>> {
+    let playlist =
+        SpotifyPlaylist::new(client, rspotify::model::PlaylistId::from_id(playlist_id)?).await?;
+    Ok(PlaylistSource::Spotify {
+        indices_not_played_yet: parking_lot::Mutex::new((0..playlist.len()).collect()),
+        playlist,
+    })
+}
+
+async fn load_youtube_playlist(
+    url: &str,
+) -> Result<PlaylistSource, Box<dyn std::error::Error + Send + Sync>> {
+    let output = tokio::process::Command::new("yt-dlp")
+        .arg("--dump-json")
+        .arg("--flat-playlist")
+        .arg(url)
+        .output()
+        .await?;
+    let output = String::from_utf8_lossy(&output.stdout);
+
+    let tracks = output.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+
+    Ok(PlaylistSource::Youtube { tracks })
+}
+
+/// Downloads `count` distinct songs from the playlist, always the same ones for the same seed.
+/// Unlike [`SongProvider`], doesn't download anything in the background
+pub async fn download_seeded(
+    client: std::sync::Arc<rspotify::ClientCredsSpotify>,
+    url: &str,
+    seed: u64,
+    count: usize,
+) -> Result<Vec<Song>, Box<dyn std::error::Error + Send + Sync>> {
+    let playlist = load_playlist_from_any_url(client, url).await?;
+    let mut indices = (0..playlist_len(&playlist)).collect::<Vec<_>>();
+    fastrand::Rng::with_seed(seed).shuffle(&mut indices);
+    indices.truncate(count);
+
+    Ok(futures::future::join_all(indices.into_iter().map(|i| download_song(&playlist, i))).await)
 }
 
 pub struct SongProvider {
     playlist: std::sync::Arc<PlaylistSource>,
-    background_downloader: parking_lot::Mutex<tokio::task::JoinHandle<Song>>,
+    /// Atomic so that it can be changed from the lobby without replacing the whole provider
+    allow_explicit: std::sync::atomic::AtomicBool,
+    /// Only started by [`SongProvider::prefetch`] or [`SongProvider::next`], so that providers
+    /// which end up unused don't download anything
+    background_downloader: parking_lot::Mutex<Option<tokio::task::JoinHandle<Option<Song>>>>,
 }
 
 impl SongProvider {
-    fn new(playlist: PlaylistSource) -> Self {
-        let playlist = std::sync::Arc::new(playlist);
-        let playlist2 = playlist.clone();
+    fn new(playlist: PlaylistSource, allow_explicit: bool) -> Self {
         Self {
-            background_downloader: parking_lot::Mutex::new(tokio::spawn(async move {
-                download_random_song(&*playlist2).await
-            })),
-            playlist,
+            playlist: std::sync::Arc::new(playlist),
+            allow_explicit: std::sync::atomic::AtomicBool::new(allow_explicit),
+            background_downloader: parking_lot::Mutex::new(None),
+        }
+    }
+
+    fn spawn_download(&self) -> tokio::task::JoinHandle<Option<Song>> {
+        let playlist = self.playlist.clone();
+        let allow_explicit = self.allow_explicit.load(std::sync::atomic::Ordering::Relaxed);
+        tokio::spawn(async move { download_random_song(&playlist, allow_explicit).await })
+    }
+
+    /// Starts downloading the first song in the background, once the provider is actually used
+    pub fn prefetch(&self) {
+        let mut background_downloader = self.background_downloader.lock();
+        if background_downloader.is_none() {
+            *background_downloader = Some(self.spawn_download());
         }
     }
 
+    /// Takes effect from the song after the one that is already being downloaded
+    pub fn set_allow_explicit(&self, allow_explicit: bool) {
+        self.allow_explicit.store(allow_explicit, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn has_release_years(&self) -> bool {
+        matches!(&*self.playlist, PlaylistSource::Spotify { .. })
+    }
+
     pub fn playlist_name(&self) -> &str {
         match &*self.playlist {
             PlaylistSource::Spotify { playlist, .. } => playlist.name(),
@@ -127,56 +305,59 @@ impl SongProvider {
     pub async fn from_any_url(
         client: std::sync::Arc<rspotify::ClientCredsSpotify>,
         url: &str,
+        allow_explicit: bool,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        use once_cell::sync::Lazy;
-        static SPOTIFY_URL_REGEX: Lazy<regex::Regex> =
-            Lazy::new(|| regex::Regex::new("spotify.com/playlist/([^?/]+)").expect("impossible"));
-        if let Some(captures) = SPOTIFY_URL_REGEX.captures(url) {
-            let playlist_id = captures.get(1).expect("impossible").as_str();
-            return Ok(Self::from_spotify_playlist(client, playlist_id).await?);
-        }
-        if url.contains("youtube.com") {
-            return Ok(Self::from_youtube_playlist(url).await?);
-        }
-        Err(format!("invalid URL: {}", url).into())
+        Ok(Self::new(load_playlist_from_any_url(client, url).await?, allow_explicit))
     }
 
     pub async fn from_spotify_playlist(
         client: std::sync::Arc<rspotify::ClientCredsSpotify>,
         playlist_id: &str,
+        allow_explicit: bool,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let playlist =
-            SpotifyPlaylist::new(client, rspotify::model::PlaylistId::from_id(playlist_id)?)
-                .await?;
-        Ok(Self::new(PlaylistSource::Spotify {
-            indices_not_played_yet: parking_lot::Mutex::new((0..playlist.len()).collect()),
-            playlist,
-        }))
+        Ok(Self::new(load_spotify_playlist(client, playlist_id).await?, allow_explicit))
     }
 
-    pub async fn from_youtube_playlist(
-        url: &str,
-    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let output = tokio::process::Command::new("yt-dlp")
-            .arg("--dump-json")
-            .arg("--flat-playlist")
-            .arg(url)
-            .output()
-            .await?;
-        let output = String::from_utf8_lossy(&output.stdout);
-
-        let tracks =
-            output.lines().map(|line| serde_json::from_str(line)).collect::<Result<_, _>>()?;
+    /// Picks up to `count` distinct random song titles from the playlist, other than `exclude`
+    pub async fn random_titles(&self, count: usize, exclude: &str) -> Vec<String> {
+        let mut titles = Vec::<String>::new();
+        // Bounded number of attempts in case the playlist doesn't have enough distinct titles
+        for _ in 0..count * 5 {
+            if titles.len() == count {
+                break;
+            }
 
-        Ok(Self::new(PlaylistSource::Youtube { tracks }))
+            let title = match &*self.playlist {
+                PlaylistSource::Spotify { playlist, .. } => {
+                    let track = playlist.track(fastrand::usize(..playlist.len())).await;
+                    match track.expect("index cant be out of bounds") {
+                        rspotify::model::PlayableItem::Track(track) => {
+                            sanitize_spotify_title(&track.name)
+                        }
+                        rspotify::model::PlayableItem::Episode(episode) => episode.name,
+                    }
+                }
+                PlaylistSource::Youtube { tracks } => {
+                    tracks[fastrand::usize(..tracks.len())].title.clone()
+                }
+            };
+            if title != exclude && !titles.contains(&title) {
+                titles.push(title);
+            }
+        }
+        titles
     }
 
-    pub async fn next(&self) -> Song {
-        let playlist = self.playlist.clone();
-        let prev_background_downloader = std::mem::replace(
-            &mut *self.background_downloader.lock(),
-            tokio::spawn(async move { download_random_song(&playlist).await }),
-        );
-        prev_background_downloader.await.expect("downloader panicked or was cancelled?")
+    /// None if explicit songs aren't allowed and the playlist has nothing else
+    pub async fn next(&self) -> Option<Song> {
+        let prev_background_downloader =
+            self.background_downloader.lock().replace(self.spawn_download());
+        match prev_background_downloader {
+            Some(downloader) => downloader.await.expect("downloader panicked or was cancelled?"),
+            None => {
+                let allow_explicit = self.allow_explicit.load(std::sync::atomic::Ordering::Relaxed);
+                download_random_song(&self.playlist, allow_explicit).await
+            }
+        }
     }
 }
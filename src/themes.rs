@@ -0,0 +1,45 @@
+use crate::utils::*;
+
+/// Preset playlist that users can pick by name instead of pasting a URL
+#[derive(serde::Deserialize)]
+pub struct Theme {
+    pub name: String,
+    /// Anything [`crate::song_provider::SongProvider::from_any_url`] accepts
+    pub url: String,
+}
+
+/// Reads the theme presets from the JSON file at `THEMES_FILE`, or `themes.json` if unset
+pub fn load_themes() -> Vec<Theme> {
+    let path = std::env::var("THEMES_FILE").unwrap_or_else(|_| "themes.json".to_string());
+    let file = match std::fs::read_to_string(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            log::info!("No theme presets loaded from {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    match serde_json::from_str::<Vec<Theme>>(&file) {
+        Ok(themes) => {
+            log::info!("Loaded {} theme presets from {}", themes.len(), path);
+            themes
+        }
+        Err(e) => {
+            log::warn!("Invalid theme presets file {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+pub fn find_theme<'a>(themes: &'a [Theme], name: &str) -> Option<&'a Theme> {
+    themes.iter().find(|theme| theme.name == name)
+}
+
+/// `<option>` elements for a theme `<select>`, preceded by an empty option with the given label
+pub fn theme_options_html(themes: &[Theme], empty_label: &str) -> String {
+    let mut html = format!("<option selected value=\"\">{}</option>", escape_html(empty_label));
+    for theme in themes {
+        let name = escape_html(&theme.name);
+        html += &format!("<option value=\"{}\">{}</option>", name, name);
+    }
+    html
+}
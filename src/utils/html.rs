@@ -0,0 +1,16 @@
+/// Makes text safe to insert into HTML, both as element content and inside quoted attributes
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+#[test]
+fn test_escape_html() {
+    assert_eq!(escape_html("Rock & Roll"), "Rock &amp; Roll");
+    assert_eq!(escape_html("<script>\"x\"</script>"), "&lt;script&gt;&quot;x&quot;&lt;/script&gt;");
+    assert_eq!(escape_html("it's"), "it&#39;s");
+}
@@ -1,5 +1,9 @@
+pub mod html;
+pub mod rate_limiter;
 pub mod spawn_attached;
 pub mod websocket;
 
+pub use html::*;
+pub use rate_limiter::*;
 pub use spawn_attached::*;
 pub use websocket::*;
@@ -0,0 +1,64 @@
+/// Counts events per key within a sliding time window, e.g. failed password attempts per IP
+pub struct RateLimiter<K> {
+    max_events: usize,
+    window: std::time::Duration,
+    events: parking_lot::Mutex<std::collections::HashMap<K, Vec<std::time::Instant>>>,
+}
+
+impl<K: std::hash::Hash + Eq> RateLimiter<K> {
+    pub fn new(max_events: usize, window: std::time::Duration) -> Self {
+        Self {
+            max_events,
+            window,
+            events: parking_lot::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Whether `key` has used up all its events in the current window
+    pub fn is_limited(&self, key: &K) -> bool {
+        let mut events = self.events.lock();
+        match events.get_mut(key) {
+            Some(times) => {
+                times.retain(|t| t.elapsed() < self.window);
+                times.len() >= self.max_events
+            }
+            None => false,
+        }
+    }
+
+    pub fn record(&self, key: K) {
+        let mut events = self.events.lock();
+        let times = events.entry(key).or_default();
+        times.retain(|t| t.elapsed() < self.window);
+        times.push(std::time::Instant::now());
+    }
+
+    /// Drops keys without any events in the current window, so the map doesn't grow forever
+    pub fn purge(&self) {
+        self.events.lock().retain(|_, times| {
+            times.retain(|t| t.elapsed() < self.window);
+            !times.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(2, std::time::Duration::from_secs(60));
+    assert!(!limiter.is_limited(&"a"));
+    limiter.record("a");
+    assert!(!limiter.is_limited(&"a"));
+    limiter.record("a");
+    assert!(limiter.is_limited(&"a"));
+    assert!(!limiter.is_limited(&"b"));
+    limiter.purge();
+    assert!(limiter.is_limited(&"a"));
+
+    // Events outside the window don't count and get purged
+    let limiter = RateLimiter::new(1, std::time::Duration::ZERO);
+    limiter.record("a");
+    assert!(!limiter.is_limited(&"a"));
+    limiter.purge();
+    assert!(limiter.events.lock().is_empty());
+}
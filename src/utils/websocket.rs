@@ -53,7 +53,7 @@ impl WebSocket {
         let send = self.send.clone();
         let is_closed = self.is_closed.clone();
         tokio::spawn(async move {
-            if let Err(_) = send.lock().await.send(msg).await {
+            if send.lock().await.send(msg).await.is_err() {
                 *is_closed.lock() = true;
             }
         });
@@ -61,6 +61,20 @@ impl WebSocket {
         Ok(())
     }
 
+    /// Sends a close frame, which lets the client tell why it was disconnected
+    pub fn close(&self, code: u16, reason: &'static str) {
+        use axum::extract::ws::{CloseFrame, Message};
+        use futures::SinkExt as _;
+
+        *self.is_closed.lock() = true;
+        let send = self.send.clone();
+        tokio::spawn(async move {
+            let frame = CloseFrame { code, reason: reason.into() };
+            // If this fails, the connection is gone anyway
+            let _ = send.lock().await.send(Message::Close(Some(frame))).await;
+        });
+    }
+
     /// Returns Err(()) when stream is closed
     pub async fn recv<T: serde::de::DeserializeOwned>(&self) -> Result<T, ()> {
         use axum::extract::ws::Message;
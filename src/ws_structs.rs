@@ -5,6 +5,12 @@ impl serde::Serialize for PlayerId {
         self.0.to_string().serialize(serializer)
     }
 }
+impl<'de> serde::Deserialize<'de> for PlayerId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map(PlayerId).map_err(serde::de::Error::custom)
+    }
+}
 
 #[derive(serde::Deserialize, Debug)]
 #[serde(tag = "type")]
@@ -17,6 +23,15 @@ pub enum ReceiveEvent {
     SkipRound,
     StopGame,
     EmoteReaction { reaction: u32 },
+    ChooseOption { index: usize },
+    NextSnippet,
+    JoinTeam { team: String },
+    LeaveTeam,
+    GrantCoHost { uuid: PlayerId },
+    RevokeCoHost { uuid: PlayerId },
+    TransferHost { uuid: PlayerId },
+    KickPlayer { uuid: PlayerId },
+    BanPlayer { uuid: PlayerId },
 }
 
 #[derive(serde::Serialize)]
@@ -30,6 +45,9 @@ pub struct SinglePlayerData {
     pub loaded: bool,
     pub guessed: bool,
     pub disconnected: bool,
+    pub team: Option<String>,
+    pub eliminated: bool,
+    pub co_host: bool,
 }
 
 #[derive(serde::Serialize)]
@@ -37,10 +55,27 @@ pub struct ScoreboardPlayer {
     pub uuid: PlayerId,
     pub display_name: String,
     pub points: u32,
+    /// Points for the guess itself. `points` also includes the streak bonus and the penalty
     pub point_diff: u32,
+    pub streak_bonus: u32,
+    pub penalty: u32,
     pub streak: u32,
 }
 
+#[derive(serde::Serialize)]
+pub struct ScoreboardTeam {
+    pub name: String,
+    pub points: u32,
+    pub point_diff: u32,
+}
+
+#[derive(serde::Serialize)]
+pub struct TeamData {
+    pub name: String,
+    pub points: u32,
+    pub members: Vec<PlayerId>,
+}
+
 #[derive(serde::Serialize)]
 pub enum ListedRoomState {
     Private,
@@ -54,6 +89,7 @@ pub struct ListedRoom {
     pub idle: u64,
     pub name: String,
     pub players: usize,
+    pub max_players: Option<u32>,
     pub status: ListedRoomState,
     pub theme: String,
 }
@@ -64,18 +100,21 @@ pub struct ListedRoom {
 pub enum SendEvent {
     FetchNew { msg: Vec<ListedRoom> },
     Join { message: String, payload: Box<SendEvent> },
-    PlayerData { payload: Vec<SinglePlayerData>, owner: PlayerId },
+    PlayerData { payload: Vec<SinglePlayerData>, owner: Option<PlayerId> },
     Chat { r#type: String, username: String, uuid: PlayerId, msg: String },
     Loading,
     Timer { message: u32, hint: String, scores: Vec<SinglePlayerData>, round_time: u32 },
     #[serde(rename = "playerTyping")] #[rustfmt::skip] PlayerTyping { uuid: PlayerId, typing: bool },
     Notify { message: String },
     NewTurn,
-    Scoreboard { payload: Vec<ScoreboardPlayer>, round: u32, max_rounds: u32 },
+    #[rustfmt::skip] Scoreboard { payload: Vec<ScoreboardPlayer>, teams: Vec<ScoreboardTeam>, round: u32, max_rounds: Option<u32>, target_score: Option<u32> },
     StartGame,
     GameEnded,
     #[serde(rename = "game-killed")] #[rustfmt::skip] GameKilled,
     GameReload,
     ResumeAudio,
     #[serde(rename = "emoteReaction")] #[rustfmt::skip] EmoteReaction { uuid: PlayerId, reaction: char },
+    TeamData { payload: Vec<TeamData> },
+    MultipleChoice { options: Vec<String> },
+    SnippetUnlocked { seconds: u32 },
 }